@@ -7,7 +7,7 @@
  */
 
 import React, {useEffect, useState} from 'react';
-import init, {transfer} from "wasm-lib";
+import init, {estimate_fee, transfer} from "wasm-lib";
 
 const Transfer = () => {
     const [privateKey, setPrivateKey] = useState('');
@@ -34,7 +34,7 @@ const Transfer = () => {
             broadcast: broadcast,
         });
         try {
-            const id = await transfer(privateKey, record, fee_record, BigInt(amount), BigInt(fee!), recipient, broadcast);
+            const id = await transfer(privateKey, record, fee_record, BigInt(amount), fee === undefined ? undefined : BigInt(fee), recipient, broadcast);
             console.log(id);
         } catch (error) {
             console.error("Failed to request records:", error);
@@ -42,6 +42,15 @@ const Transfer = () => {
 
     };
 
+    const handleEstimateFee = async () => {
+        const estimate = await estimate_fee(privateKey, record, BigInt(amount), recipient);
+        if (estimate.startsWith("error: ")) {
+            console.error("Failed to estimate the fee:", estimate);
+            return;
+        }
+        setFee(JSON.parse(estimate).recommended_fee);
+    };
+
     const handleInputChange = <T, >(
         event: React.ChangeEvent<HTMLInputElement>,
         setState: React.Dispatch<React.SetStateAction<T>>
@@ -117,6 +126,7 @@ const Transfer = () => {
                     value={fee === undefined ? '' : fee}
                     onChange={(e) => handleInputChange<number | undefined>(e, setFee)}
                 />
+                <button type="button" onClick={handleEstimateFee}>估算Fee</button>
                 <br/>
                 <br/>
 
@@ -0,0 +1,109 @@
+//! The request and response types of vm-server, shared with its clients.
+//!
+//! Every request carries the `network` it is for and the protocol `version` of the client.
+
+mod requests;
+mod responses;
+
+pub use requests::*;
+pub use responses::*;
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use snarkvm_console_network::Network;
+
+/// The version of the protocol, bumped on every breaking change to the types of this crate.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Returns the name of the network `N`, as used in the node routes, or `None` if it is not supported.
+pub fn network_name<N: Network>() -> Option<&'static str> {
+    match N::ID {
+        3 => Some("testnet3"),
+        _ => None,
+    }
+}
+
+/// Returns the `network` field of the requests for the network `N`.
+fn network<N: Network>() -> String {
+    network_name::<N>().map_or_else(|| N::ID.to_string(), str::to_string)
+}
+
+/// The fields every request carries, read before the rest of the request so an incompatible
+/// client gets a clear error instead of a parse failure.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+impl Header {
+    /// Ensures the request is for the network `N` and speaks the protocol version of this crate.
+    pub fn check<N: Network>(&self) -> Result<(), ProtocolError> {
+        if self.version != Some(PROTOCOL_VERSION) {
+            return Err(ProtocolError::UnsupportedVersion { supported: PROTOCOL_VERSION, found: self.version });
+        }
+        let expected = network::<N>();
+        if self.network.as_deref() != Some(expected.as_str()) {
+            return Err(ProtocolError::WrongNetwork { expected, found: self.network.clone() });
+        }
+        Ok(())
+    }
+}
+
+/// Why a request cannot be handled by the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// The request is for another network.
+    WrongNetwork { expected: String, found: Option<String> },
+    /// The client speaks another protocol version, or predates versioning.
+    UnsupportedVersion { supported: u32, found: Option<u32> },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::WrongNetwork { expected, found: Some(found) } => {
+                write!(f, "the request is for network '{}', this server proves for '{}'", found, expected)
+            }
+            ProtocolError::WrongNetwork { expected, found: None } => {
+                write!(f, "the request does not set 'network', this server proves for '{}'", expected)
+            }
+            ProtocolError::UnsupportedVersion { supported, found: Some(found) } if found < supported => {
+                write!(f, "the client speaks protocol version {}, this server requires version {}; upgrade the client", found, supported)
+            }
+            ProtocolError::UnsupportedVersion { supported, found: Some(found) } => {
+                write!(f, "the client speaks protocol version {}, this server only supports version {}", found, supported)
+            }
+            ProtocolError::UnsupportedVersion { supported, found: None } => {
+                write!(f, "the request does not set 'version', the client predates protocol version {}; upgrade the client", supported)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console_network::Testnet3;
+
+    #[test]
+    fn test_header_check() {
+        let header = |network: Option<&str>, version: Option<u32>| Header { network: network.map(str::to_string), version };
+
+        assert_eq!(header(Some("testnet3"), Some(PROTOCOL_VERSION)).check::<Testnet3>(), Ok(()));
+        assert!(matches!(header(Some("mainnet"), Some(PROTOCOL_VERSION)).check::<Testnet3>(), Err(ProtocolError::WrongNetwork { .. })));
+        assert!(matches!(header(None, Some(PROTOCOL_VERSION)).check::<Testnet3>(), Err(ProtocolError::WrongNetwork { found: None, .. })));
+        assert!(matches!(header(Some("testnet3"), None).check::<Testnet3>(), Err(ProtocolError::UnsupportedVersion { found: None, .. })));
+        assert!(matches!(header(Some("testnet3"), Some(0)).check::<Testnet3>(), Err(ProtocolError::UnsupportedVersion { .. })));
+
+        // The header is read from any request, unversioned ones included.
+        let unversioned = serde_json::from_str::<Header>(r#"{ "request": "ignored", "fee": 10 }"#).unwrap();
+        assert_eq!(unversioned, Header::default());
+        let error = unversioned.check::<Testnet3>().unwrap_err().to_string();
+        assert!(error.contains("upgrade the client"), "{}", error);
+    }
+}
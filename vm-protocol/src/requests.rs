@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use snarkvm_console_network::Network;
+use snarkvm_console_program::Request;
+use snarkvm_synthesizer::{Execution, Fee, Transaction};
+use crate::{network, PROTOCOL_VERSION};
+
+/// How a fee is paid, set by `fee_kind` in the requests with a fee.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeKind {
+    /// `credits.aleo/fee`, paid from a private fee record.
+    #[default]
+    Private,
+    /// `credits.aleo/fee_public`, paid from the public balance in the `account` mapping.
+    Public,
+}
+
+/// The body of `POST /execute_function`, `/execute_and_broadcast` and `/jobs`, and of each item
+/// of `/execute_batch`. The fee fields are set together, or not at all.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ExecuteRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    pub request: Request<N>,
+    /// The signed requests of the calls the function makes to other programs, in the order
+    /// `Process::authorize` returns them after `request`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<Request<N>>,
+    #[serde(default)]
+    pub fee_kind: FeeKind,
+    pub fee_request: Option<Request<N>>,
+    pub fee_record: Option<String>,
+    /// The fee the fee request pays, in gates.
+    pub fee: Option<u64>,
+}
+
+impl<N: Network> ExecuteRequest<N> {
+    /// Initializes a request without a fee.
+    pub fn new(request: Request<N>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, request, calls: Vec::new(), fee_kind: FeeKind::Private, fee_request: None, fee_record: None, fee: None }
+    }
+
+    /// Adds the signed requests of the calls the function makes.
+    pub fn with_calls(mut self, calls: Vec<Request<N>>) -> Self {
+        self.calls = calls;
+        self
+    }
+
+    /// Returns the signed requests of the execution in call order, as an `Authorization` holds them.
+    pub fn requests(&self) -> Vec<Request<N>> {
+        std::iter::once(&self.request).chain(&self.calls).cloned().collect()
+    }
+
+    /// Pays `fee` gates from the fee record with the signed fee request.
+    pub fn with_fee(mut self, fee_request: Request<N>, fee_record: String, fee: u64) -> Self {
+        (self.fee_request, self.fee_record, self.fee) = (Some(fee_request), Some(fee_record), Some(fee));
+        self
+    }
+}
+
+/// The body of `POST /estimate_fee`, the request is signed but never proved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct EstimateFeeRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    pub request: Request<N>,
+    /// The signed requests of the calls the function makes, as in `ExecuteRequest`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<Request<N>>,
+}
+
+impl<N: Network> EstimateFeeRequest<N> {
+    pub fn new(request: Request<N>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, request, calls: Vec::new() }
+    }
+
+    /// Adds the signed requests of the calls the function makes.
+    pub fn with_calls(mut self, calls: Vec<Request<N>>) -> Self {
+        self.calls = calls;
+        self
+    }
+}
+
+/// The body of `POST /deploy`, the fee is required.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct DeployRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    /// The source of the program.
+    pub program: String,
+    #[serde(default)]
+    pub fee_kind: FeeKind,
+    pub fee_request: Request<N>,
+    pub fee_record: String,
+    pub fee: Option<u64>,
+}
+
+impl<N: Network> DeployRequest<N> {
+    pub fn new(program: String, fee_request: Request<N>, fee_record: String, fee: Option<u64>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, program, fee_kind: FeeKind::Private, fee_request, fee_record, fee }
+    }
+}
+
+/// The body of `POST /prove_execution`, the execution is proved without a fee.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProveExecutionRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    pub request: Request<N>,
+    /// The signed requests of the calls the function makes, as in `ExecuteRequest`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<Request<N>>,
+}
+
+impl<N: Network> ProveExecutionRequest<N> {
+    pub fn new(request: Request<N>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, request, calls: Vec::new() }
+    }
+
+    /// Adds the signed requests of the calls the function makes.
+    pub fn with_calls(mut self, calls: Vec<Request<N>>) -> Self {
+        self.calls = calls;
+        self
+    }
+}
+
+/// The body of `POST /prove_fee`, the fee may be paid by another account than the execution.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProveFeeRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    /// The execution the fee pays for.
+    pub execution_id: N::TransactionID,
+    #[serde(default)]
+    pub fee_kind: FeeKind,
+    pub fee_request: Request<N>,
+    pub fee_record: String,
+    pub fee: Option<u64>,
+}
+
+impl<N: Network> ProveFeeRequest<N> {
+    pub fn new(execution_id: N::TransactionID, fee_request: Request<N>, fee_record: String, fee: Option<u64>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, execution_id, fee_kind: FeeKind::Private, fee_request, fee_record, fee }
+    }
+}
+
+/// The body of `POST /assemble_transaction`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct AssembleRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    pub execution: Execution<N>,
+    pub fee: Option<Fee<N>>,
+}
+
+impl<N: Network> AssembleRequest<N> {
+    pub fn new(execution: Execution<N>, fee: Option<Fee<N>>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, execution, fee }
+    }
+}
+
+/// The body of `POST /verify_transaction`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct VerifyTransactionRequest<N: Network> {
+    pub network: String,
+    pub version: u32,
+    pub transaction: Transaction<N>,
+}
+
+impl<N: Network> VerifyTransactionRequest<N> {
+    pub fn new(transaction: Transaction<N>) -> Self {
+        Self { network: network::<N>(), version: PROTOCOL_VERSION, transaction }
+    }
+}
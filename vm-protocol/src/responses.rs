@@ -0,0 +1,138 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use snarkvm_console_network::Network;
+use snarkvm_synthesizer::{Execution, Fee, Program, Transaction};
+
+/// The JSON body of every error response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// A stable, machine-readable error code.
+    pub code: String,
+    /// A short human-readable description of the error.
+    pub message: String,
+    /// The underlying cause, if any.
+    pub details: Option<String>,
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.details {
+            Some(details) => write!(f, "{} ({}): {}", self.message, self.code, details),
+            None => write!(f, "{} ({})", self.message, self.code),
+        }
+    }
+}
+
+/// The fee estimate of an execution, returned by `POST /estimate_fee`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// The estimated size of the execution, in bytes.
+    pub execution_size: u64,
+    /// The number of instructions of the function.
+    pub num_instructions: usize,
+    /// The number of constraints of the function circuit.
+    pub num_constraints: usize,
+    /// The minimum fee accepted for the execution, in gates.
+    pub minimum_fee: u64,
+    /// The fee covering the execution and the fee transition, in gates.
+    pub recommended_fee: u64,
+}
+
+/// The response of `POST /prove_execution`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProvedExecution<N: Network> {
+    /// The ID of the execution, as a transaction without a fee.
+    pub execution_id: String,
+    pub execution: Execution<N>,
+    /// The minimum fee to assemble the execution with, in gates.
+    pub minimum_fee: u64,
+}
+
+/// The response of `POST /prove_fee`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProvedFee<N: Network> {
+    pub execution_id: String,
+    pub fee: Fee<N>,
+    /// The amount of the fee, in gates.
+    pub amount: u64,
+}
+
+/// The result of one request of `POST /execute_batch`, either a transaction or an error.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct BatchItem<N: Network> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction<N>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+/// The outcome of broadcasting a transaction to one node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeResult {
+    /// The base URL of the node.
+    pub node: String,
+    /// `true` if the node accepted the transaction under the expected ID.
+    pub accepted: bool,
+    /// The number of requests sent to the node.
+    pub attempts: u32,
+    /// The transaction ID returned by the node.
+    pub id: Option<String>,
+    /// The reason the node did not accept the transaction.
+    pub error: Option<String>,
+}
+
+/// The response of `POST /execute_and_broadcast`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct BroadcastResponse<N: Network> {
+    pub id: String,
+    pub transaction: Transaction<N>,
+    pub nodes: Vec<NodeResult>,
+}
+
+/// The response of `GET /info`, clients check it before sending requests.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// The version of vm-server.
+    pub version: String,
+    /// The protocol version of the requests the server accepts.
+    pub protocol_version: u32,
+    /// The version of snarkVM the transactions are proved with.
+    pub snarkvm_version: String,
+    /// The ID of the network, `3` for Testnet3.
+    pub network_id: u16,
+    /// The name of the network, as used in the node routes.
+    pub network: String,
+    /// `true` once the warm-up completed.
+    pub ready: bool,
+    /// The loaded programs.
+    pub programs: Vec<ProgramInfo>,
+    pub query_endpoint: EndpointStatus,
+}
+
+/// A loaded program and its functions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramInfo {
+    pub id: String,
+    pub functions: Vec<String>,
+}
+
+impl<N: Network> From<&Program<N>> for ProgramInfo {
+    fn from(program: &Program<N>) -> Self {
+        Self { id: program.id().to_string(), functions: program.functions().keys().map(|name| name.to_string()).collect() }
+    }
+}
+
+/// Whether the query endpoint answers, checked by fetching its latest height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,21 @@
+//! Exposes the snarkVM version resolved in `Cargo.lock` to the server as `SNARKVM_VERSION`.
+
+use std::path::Path;
+
+fn main() {
+    let lock = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+    let version = std::fs::read_to_string(&lock)
+        .ok()
+        .and_then(|lock| locked_version(&lock, "snarkvm-synthesizer"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=SNARKVM_VERSION={}", version);
+}
+
+/// Returns the version of the package in the lock file.
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name = format!("name = \"{}\"", package);
+    let mut lines = lock.lines().skip_while(|line| line.trim() != name).skip(1);
+    let version = lines.next()?.trim().strip_prefix("version = \"")?.strip_suffix('"')?;
+    Some(version.to_string())
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snarkvm_console_network::Network;
+use vm_protocol::Header;
+use warp::http::{HeaderMap, Method};
+use warp::hyper::body::Bytes;
+use warp::path::FullPath;
+use warp::{reject, Filter, Rejection};
+use crate::error::RestError;
+
+/// The header carrying a plain API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// The header naming the key of a signed request.
+pub const KEY_ID_HEADER: &str = "x-api-key-id";
+/// The header carrying the UNIX time a request was signed at.
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+/// The header carrying the hex HMAC-SHA256 of a signed request.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// How far the timestamp of a signed request may be from the server clock, in seconds.
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+/// The window of the proving quotas.
+const QUOTA_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+/// The window of the rate limits.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A client allowed to use the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    /// The name of the client, sent in `x-api-key-id` with signed requests.
+    pub name: String,
+    /// The secret, sent in `x-api-key` or used to sign requests.
+    pub key: String,
+    /// The maximum number of requests per minute, unlimited if unset.
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// The maximum number of proving requests per day, unlimited if unset.
+    #[serde(default)]
+    pub proofs_per_day: Option<u32>,
+}
+
+#[derive(Debug)]
+struct Usage {
+    requests_since: Instant,
+    requests: u32,
+    proofs_since: Instant,
+    proofs: u32,
+}
+
+/// Authenticates requests against the configured API keys and enforces their limits.
+///
+/// Requests either carry the key in `x-api-key`, or are signed with it: `x-signature` is the hex
+/// HMAC-SHA256 of `"{x-timestamp}\n{method}\n{path}\n{body}"`, keyed with the secret of the client
+/// named in `x-api-key-id`. A signature is accepted once. Without configured keys, every request
+/// is allowed.
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    keys: Arc<Vec<ApiKey>>,
+    usage: Arc<Mutex<HashMap<String, Usage>>>,
+    /// The signatures accepted, with their timestamp, until it leaves the clock skew window.
+    signatures: Arc<Mutex<HashMap<Vec<u8>, u64>>>,
+}
+
+impl Auth {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        Self { keys: Arc::new(keys), usage: Default::default(), signatures: Default::default() }
+    }
+
+    /// Authenticates the request and counts it against the limits of its key.
+    /// Returns the name of the client, or `None` if authentication is disabled.
+    pub fn authorize(&self, method: &Method, path: &str, headers: &HeaderMap, body: &[u8], proving: bool) -> Result<Option<String>, RestError> {
+        self.authorize_proofs(method, path, headers, body, u32::from(proving))
+    }
+
+    /// Like `authorize`, counting `proofs` proofs against the quota of the key.
+    pub fn authorize_proofs(&self, method: &Method, path: &str, headers: &HeaderMap, body: &[u8], proofs: u32) -> Result<Option<String>, RestError> {
+        if self.keys.is_empty() {
+            return Ok(None);
+        }
+        let key = self.authenticate(method, path, headers, body)?;
+        self.record(key, proofs)?;
+        Ok(Some(key.name.clone()))
+    }
+
+    fn authenticate(&self, method: &Method, path: &str, headers: &HeaderMap, body: &[u8]) -> Result<&ApiKey, RestError> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(secret) = header(API_KEY_HEADER) {
+            // Compare digests, so the comparison time does not depend on the secret.
+            let digest = Sha256::digest(secret.as_bytes());
+            return self
+                .keys
+                .iter()
+                .find(|key| Sha256::digest(key.key.as_bytes()) == digest)
+                .ok_or_else(|| RestError::Unauthorized("the API key is invalid".to_string()));
+        }
+
+        let (name, timestamp, signature) = match (header(KEY_ID_HEADER), header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)) {
+            (Some(name), Some(timestamp), Some(signature)) => (name, timestamp, signature),
+            _ => return Err(RestError::Unauthorized(format!("set '{}', or sign the request", API_KEY_HEADER))),
+        };
+        let key = self.keys.iter().find(|key| key.name == name).ok_or_else(|| RestError::Unauthorized(format!("unknown key '{}'", name)))?;
+        let signed_at = timestamp.parse::<u64>().map_err(|_| RestError::Unauthorized(format!("invalid '{}'", TIMESTAMP_HEADER)))?;
+        let now = now_secs();
+        if now.abs_diff(signed_at) > MAX_CLOCK_SKEW_SECS {
+            return Err(RestError::Unauthorized("the request signature has expired".to_string()));
+        }
+        let signature = hex::decode(signature).map_err(|_| RestError::Unauthorized(format!("invalid '{}'", SIGNATURE_HEADER)))?;
+        signer(&key.key, timestamp, method, path, body)
+            .verify_slice(&signature)
+            .map_err(|_| RestError::Unauthorized("the request signature is invalid".to_string()))?;
+
+        // Refuse a replayed signature, expired signatures are refused above so they can be forgotten.
+        let mut signatures = self.signatures.lock();
+        signatures.retain(|_, signed_at| now.abs_diff(*signed_at) <= MAX_CLOCK_SKEW_SECS);
+        if signatures.insert(signature, signed_at).is_some() {
+            return Err(RestError::Unauthorized("the request signature was already used".to_string()));
+        }
+        Ok(key)
+    }
+
+    /// Counts a request and its proofs against the limits of the key.
+    fn record(&self, key: &ApiKey, proofs: u32) -> Result<(), RestError> {
+        let mut usage = self.usage.lock();
+        let usage = Self::usage_of(&mut usage, key);
+        if let Some(limit) = key.requests_per_minute.filter(|limit| usage.requests >= *limit) {
+            return Err(RestError::RateLimited(format!("'{}' is limited to {} requests per minute", key.name, limit)));
+        }
+        Self::check_quota(usage, key, proofs)?;
+        usage.requests += 1;
+        usage.proofs += proofs;
+        Ok(())
+    }
+
+    /// Counts the proofs of a request already recorded against the quota of the key.
+    fn record_proofs(&self, key: &ApiKey, proofs: u32) -> Result<(), RestError> {
+        let mut usage = self.usage.lock();
+        let usage = Self::usage_of(&mut usage, key);
+        Self::check_quota(usage, key, proofs)?;
+        usage.proofs += proofs;
+        Ok(())
+    }
+
+    /// Returns the usage of the key, reset once its windows are over.
+    fn usage_of<'a>(usage: &'a mut HashMap<String, Usage>, key: &ApiKey) -> &'a mut Usage {
+        let now = Instant::now();
+        let usage = usage.entry(key.name.clone()).or_insert(Usage { requests_since: now, requests: 0, proofs_since: now, proofs: 0 });
+        if now.duration_since(usage.requests_since) >= RATE_WINDOW {
+            (usage.requests_since, usage.requests) = (now, 0);
+        }
+        if now.duration_since(usage.proofs_since) >= QUOTA_WINDOW {
+            (usage.proofs_since, usage.proofs) = (now, 0);
+        }
+        usage
+    }
+
+    fn check_quota(usage: &Usage, key: &ApiKey, proofs: u32) -> Result<(), RestError> {
+        match key.proofs_per_day.filter(|quota| proofs > 0 && usage.proofs.saturating_add(proofs) > *quota) {
+            Some(quota) => Err(RestError::QuotaExceeded(format!("'{}' is limited to {} proofs per day", key.name, quota))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the HMAC of the signed request, keyed with the secret.
+fn signer(secret: &str, timestamp: &str, method: &Method, path: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}\n{}\n{}\n", timestamp, method, path).as_bytes());
+    mac.update(body);
+    mac
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs())
+}
+
+/// Parses a request of the protocol, checking its network and version before the rest of it.
+pub fn parse_request<N: Network, T: DeserializeOwned>(body: &[u8]) -> Result<T, RestError> {
+    let header = serde_json::from_slice::<Header>(body).map_err(|e| RestError::BadRequest(format!("the request is not a JSON object: {}", e)))?;
+    header.check::<N>()?;
+    serde_json::from_slice::<T>(body).map_err(|e| RestError::BadRequest(e.to_string()))
+}
+
+/// Authenticates the request and extracts its JSON body, `proving` requests count against the quota.
+pub fn json_body<N: Network, T: DeserializeOwned + Send>(auth: Auth, body_limit: u64, proving: bool) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    client_json_body::<N, T>(auth, body_limit, proving).map(|_: Option<String>, request: T| request)
+}
+
+/// Like `json_body`, also extracting the name of the client, `None` if authentication is disabled.
+pub fn client_json_body<N: Network, T: DeserializeOwned + Send>(auth: Auth, body_limit: u64, proving: bool) -> impl Filter<Extract = (Option<String>, T), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::content_length_limit(body_limit))
+        .and(warp::body::bytes())
+        .and_then(move |method: Method, path: FullPath, headers: HeaderMap, body: Bytes| {
+            let auth = auth.clone();
+            async move {
+                let client = auth.authorize(&method, path.as_str(), &headers, &body, proving).map_err(reject::custom)?;
+                let request = parse_request::<N, T>(&body).map_err(reject::custom)?;
+                Ok::<_, Rejection>((client, request))
+            }
+        })
+        .untuple_one()
+}
+
+/// Authenticates the request and extracts its JSON array body of 1 to `max_batch` items. Each item
+/// is parsed on its own, so an invalid item fails alone, and each valid item counts against the quota.
+pub fn json_batch<N: Network, T: DeserializeOwned + Send>(auth: Auth, body_limit: u64, max_batch: usize) -> impl Filter<Extract = (Vec<Result<T, RestError>>,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::content_length_limit(body_limit))
+        .and(warp::body::bytes())
+        .and_then(move |method: Method, path: FullPath, headers: HeaderMap, body: Bytes| {
+            let auth = auth.clone();
+            async move {
+                // Count the request before parsing the body, its proofs are only known once it is parsed.
+                let key = match auth.keys.is_empty() {
+                    true => None,
+                    false => {
+                        let key = auth.authenticate(&method, path.as_str(), &headers, &body).map_err(reject::custom)?;
+                        auth.record(key, 0).map_err(reject::custom)?;
+                        Some(key)
+                    }
+                };
+                let items = serde_json::from_slice::<Vec<&serde_json::value::RawValue>>(&body)
+                    .map_err(|e| reject::custom(RestError::BadRequest(e.to_string())))?;
+                if items.is_empty() || items.len() > max_batch {
+                    return Err(reject::custom(RestError::BadRequest(format!("a batch holds 1 to {} requests, got {}", max_batch, items.len()))));
+                }
+                let items = items
+                    .into_iter()
+                    .map(|item| parse_request::<N, T>(item.get().as_bytes()))
+                    .collect::<Vec<_>>();
+                let proofs = u32::try_from(items.iter().filter(|item| item.is_ok()).count()).unwrap_or(u32::MAX);
+                if let Some(key) = key {
+                    auth.record_proofs(key, proofs).map_err(reject::custom)?;
+                }
+                Ok::<_, Rejection>(items)
+            }
+        })
+}
+
+/// Authenticates a request without a body and extracts the name of its client, `None` if
+/// authentication is disabled.
+pub fn check(auth: Auth) -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and_then(move |method: Method, path: FullPath, headers: HeaderMap| {
+            let auth = auth.clone();
+            async move { auth.authorize(&method, path.as_str(), &headers, &[], false).map_err(reject::custom) }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::http::StatusCode;
+    use crate::error::{handle_rejection, ErrorResponse};
+    use crate::CurrentNetwork;
+
+    /// A request of the protocol, echoed by the test routes.
+    #[derive(Deserialize)]
+    struct Echo {
+        message: String,
+    }
+
+    fn auth(requests_per_minute: Option<u32>, proofs_per_day: Option<u32>) -> Auth {
+        Auth::new(vec![ApiKey { name: "wallet".to_string(), key: "secret".to_string(), requests_per_minute, proofs_per_day }])
+    }
+
+    fn sign(secret: &str, timestamp: u64, path: &str, body: &str) -> String {
+        hex::encode(signer(secret, &timestamp.to_string(), &Method::POST, path, body.as_bytes()).finalize().into_bytes())
+    }
+
+    async fn reply(filter: &(impl Filter<Extract = (String,), Error = Rejection> + Clone + 'static), request: warp::test::RequestBuilder) -> (StatusCode, String) {
+        let reply = request.method("POST").path("/prove").reply(&filter.clone().recover(handle_rejection)).await;
+        let body = match reply.status() {
+            StatusCode::OK => String::from_utf8(reply.body().to_vec()).unwrap(),
+            _ => serde_json::from_slice::<ErrorResponse>(reply.body()).unwrap().code,
+        };
+        (reply.status(), body)
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_and_signatures() {
+        let filter = warp::path!("prove").and(json_body::<CurrentNetwork, Echo>(auth(None, None), 1024, true)).map(|echo: Echo| echo.message);
+        let body = r#"{ "network": "testnet3", "version": 1, "message": "hello" }"#;
+
+        let (status, code) = reply(&filter, warp::test::request().body(body)).await;
+        assert_eq!((status, code.as_str()), (StatusCode::UNAUTHORIZED, "unauthorized"));
+        let (status, _) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "wrong").body(body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, echoed) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(body)).await;
+        assert_eq!((status, echoed.as_str()), (StatusCode::OK, "hello"));
+
+        // Requests of other networks and unversioned clients are rejected before being parsed.
+        let authorized = |body: &'static str| warp::test::request().header(API_KEY_HEADER, "secret").body(body);
+        let (status, code) = reply(&filter, authorized(r#"{ "network": "mainnet", "version": 1, "message": "hello" }"#)).await;
+        assert_eq!((status, code.as_str()), (StatusCode::BAD_REQUEST, "wrong_network"));
+        let (status, code) = reply(&filter, authorized(r#"{ "request": "hello" }"#)).await;
+        assert_eq!((status, code.as_str()), (StatusCode::BAD_REQUEST, "unsupported_version"));
+
+        let signed = |timestamp: u64, signature: String| {
+            warp::test::request()
+                .header(KEY_ID_HEADER, "wallet")
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, signature)
+                .body(body)
+        };
+        let now = now_secs();
+        assert_eq!(reply(&filter, signed(now, sign("secret", now, "/prove", body))).await.0, StatusCode::OK);
+        // A signature is accepted once, a captured request cannot be replayed.
+        assert_eq!(reply(&filter, signed(now, sign("secret", now, "/prove", body))).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(reply(&filter, signed(now + 1, sign("secret", now + 1, "/prove", body))).await.0, StatusCode::OK);
+        // The signature covers the body and expires.
+        assert_eq!(reply(&filter, signed(now, sign("secret", now, "/prove", "{}"))).await.0, StatusCode::UNAUTHORIZED);
+        let old = now - 2 * MAX_CLOCK_SKEW_SECS;
+        assert_eq!(reply(&filter, signed(old, sign("secret", old, "/prove", body))).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_batch_items_fail_alone() {
+        let filter = warp::path!("prove").and(json_batch::<CurrentNetwork, Echo>(auth(None, Some(1)), 1024, 2)).map(|items: Vec<Result<Echo, RestError>>| {
+            items.into_iter().map(|item| item.map_or_else(|error| error.to_response().code, |echo| echo.message)).collect::<Vec<_>>().join(",")
+        });
+        let batch = r#"[{ "network": "testnet3", "version": 1, "message": "hello" }, { "network": "mainnet", "version": 1, "message": "hello" }]"#;
+        let (status, items) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(batch)).await;
+        assert_eq!((status, items.as_str()), (StatusCode::OK, "hello,wrong_network"));
+
+        // Only the valid item counted against the quota, malformed items fail alone too.
+        let batch = r#"[{ "network": "testnet3", "version": 1 }, 42]"#;
+        let (status, items) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(batch)).await;
+        assert_eq!((status, items.as_str()), (StatusCode::OK, "bad_request,bad_request"));
+        // A signed batch is authenticated once.
+        let now = now_secs();
+        let signed = warp::test::request()
+            .header(KEY_ID_HEADER, "wallet")
+            .header(TIMESTAMP_HEADER, now.to_string())
+            .header(SIGNATURE_HEADER, sign("secret", now, "/prove", batch))
+            .body(batch);
+        assert_eq!(reply(&filter, signed).await, (StatusCode::OK, "bad_request,bad_request".to_string()));
+        let (status, code) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body("{}")).await;
+        assert_eq!((status, code.as_str()), (StatusCode::BAD_REQUEST, "bad_request"));
+
+        // Empty and oversized batches are refused before counting against the quota.
+        for batch in ["[]", "[1, 2, 3]"] {
+            let (status, code) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(batch)).await;
+            assert_eq!((status, code.as_str()), (StatusCode::BAD_REQUEST, "bad_request"));
+        }
+        let batch = r#"[{ "network": "testnet3", "version": 1, "message": "hello" }]"#;
+        let (status, items) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(batch)).await;
+        assert_eq!((status, items.as_str()), (StatusCode::TOO_MANY_REQUESTS, "quota_exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_batches_are_rate_limited() {
+        let filter = warp::path!("prove").and(json_batch::<CurrentNetwork, Echo>(auth(Some(3), None), 1024, 2)).map(|items: Vec<Result<Echo, RestError>>| items.len().to_string());
+        for batch in ["{}", "[]", "[1, 2, 3]"] {
+            let (status, code) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body(batch)).await;
+            assert_eq!((status, code.as_str()), (StatusCode::BAD_REQUEST, "bad_request"));
+        }
+        // Like other requests, they counted against the rate limit before being parsed.
+        let (status, code) = reply(&filter, warp::test::request().header(API_KEY_HEADER, "secret").body("[1]")).await;
+        assert_eq!((status, code.as_str()), (StatusCode::TOO_MANY_REQUESTS, "rate_limited"));
+    }
+
+    #[test]
+    fn test_rate_limits_and_quotas() {
+        let headers = |secret: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(API_KEY_HEADER, secret.parse().unwrap());
+            headers
+        };
+        let authorize = |auth: &Auth, proving: bool| auth.authorize(&Method::POST, "/prove", &headers("secret"), &[], proving);
+
+        let limited = auth(Some(2), None);
+        assert_eq!(authorize(&limited, false).unwrap().as_deref(), Some("wallet"));
+        authorize(&limited, false).unwrap();
+        assert!(matches!(authorize(&limited, false), Err(RestError::RateLimited(_))));
+
+        let quota = auth(None, Some(1));
+        authorize(&quota, true).unwrap();
+        assert!(matches!(authorize(&quota, true), Err(RestError::QuotaExceeded(_))));
+        // Requests that do not prove stay allowed.
+        authorize(&quota, false).unwrap();
+
+        // A batch counts each of its proofs.
+        let batch = |auth: &Auth, proofs: u32| auth.authorize_proofs(&Method::POST, "/prove", &headers("secret"), &[], proofs);
+        let quota = auth(None, Some(3));
+        batch(&quota, 2).unwrap();
+        assert!(matches!(batch(&quota, 2), Err(RestError::QuotaExceeded(_))));
+        batch(&quota, 1).unwrap();
+
+        assert_eq!(Auth::default().authorize(&Method::POST, "/prove", &HeaderMap::new(), &[], true).unwrap(), None);
+    }
+}
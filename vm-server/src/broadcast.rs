@@ -0,0 +1,130 @@
+use std::time::Duration;
+use snarkvm_console_network::Network;
+use snarkvm_synthesizer::Transaction;
+use vm_protocol::NodeResult;
+use crate::config::network_name;
+use crate::error::RestError;
+use crate::query::UPSTREAM_TIMEOUT;
+
+/// Relays proven transactions to the configured nodes.
+#[derive(Clone, Debug)]
+pub struct Broadcaster {
+    nodes: Vec<String>,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Broadcaster {
+    /// Initializes a broadcaster retrying each node up to `retries` times after a failed attempt.
+    pub fn new(nodes: Vec<String>, retries: u32, retry_delay: Duration) -> Self {
+        Self { nodes, retries, retry_delay }
+    }
+
+    /// Returns `true` if no node is configured.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Sends the transaction to every node concurrently and returns the result of each node.
+    pub async fn broadcast<N: Network>(&self, transaction: &Transaction<N>) -> Result<Vec<NodeResult>, RestError> {
+        let network = network_name::<N>().map_err(|e| RestError::Broadcast(e.to_string()))?;
+        let body = serde_json::to_string(transaction).map_err(|e| RestError::Broadcast(e.to_string()))?;
+        Ok(self.broadcast_raw(network, body, transaction.id().to_string()).await)
+    }
+
+    async fn broadcast_raw(&self, network: &str, body: String, expected_id: String) -> Vec<NodeResult> {
+        let tasks = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let (broadcaster, node) = (self.clone(), node.clone());
+                let url = format!("{}/{}/transaction/broadcast", node, network);
+                let (body, expected_id) = (body.clone(), expected_id.clone());
+                tokio::task::spawn_blocking(move || broadcaster.send(node, &url, &body, &expected_id))
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for (task, node) in tasks.into_iter().zip(&self.nodes) {
+            results.push(task.await.unwrap_or_else(|e| NodeResult {
+                node: node.clone(),
+                accepted: false,
+                attempts: 0,
+                id: None,
+                error: Some(format!("the broadcast panicked: {}", e)),
+            }));
+        }
+        results
+    }
+
+    /// Posts the transaction to the node, retrying transport errors and server errors.
+    fn send(&self, node: String, url: &str, body: &str, expected_id: &str) -> NodeResult {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (retry, id, error) = match ureq::post(url).timeout(UPSTREAM_TIMEOUT).set("content-type", "application/json").send_string(body) {
+                Ok(response) => match response.into_string() {
+                    Ok(id) => {
+                        let id = id.trim().trim_matches('"').to_string();
+                        let error = (id != expected_id).then(|| format!("the node returned ID '{}' instead of '{}'", id, expected_id));
+                        (false, Some(id), error)
+                    }
+                    Err(e) => (true, None, Some(format!("failed to read the response: {}", e))),
+                },
+                Err(ureq::Error::Status(status, response)) => {
+                    let details = response.into_string().unwrap_or_default();
+                    (status >= 500, None, Some(format!("the node rejected the transaction ({}): {}", status, details)))
+                }
+                Err(e) => (true, None, Some(e.to_string())),
+            };
+
+            if !retry || attempts > self.retries {
+                if let Some(error) = &error {
+                    tracing::warn!("Failed to broadcast to '{}': {}", node, error);
+                }
+                return NodeResult { node, accepted: error.is_none(), attempts, id, error };
+            }
+            std::thread::sleep(self.retry_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    /// Serves a node answering with `reply` to each broadcast, after failing the first `failures` times.
+    async fn serve_node(reply: &'static str, failures: u32) -> String {
+        let calls = Arc::new(AtomicU32::new(0));
+        let route = warp::post().and(warp::path!("testnet3" / "transaction" / "broadcast")).and(warp::body::bytes()).map(move |_| {
+            match calls.fetch_add(1, Ordering::SeqCst) < failures {
+                true => warp::reply::with_status(warp::reply::json(&"unavailable"), StatusCode::SERVICE_UNAVAILABLE),
+                false => warp::reply::with_status(warp::reply::json(&reply), StatusCode::OK),
+            }
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_broadcast_results() {
+        let accepting = serve_node("at1expected", 1).await;
+        let mismatching = serve_node("at1other", 0).await;
+        let unavailable = serve_node("at1expected", 10).await;
+
+        let broadcaster = Broadcaster::new(vec![accepting.clone(), mismatching, unavailable], 2, Duration::ZERO);
+        let results = broadcaster.broadcast_raw("testnet3", "{}".to_string(), "at1expected".to_string()).await;
+
+        assert_eq!(results[0], NodeResult { node: accepting, accepted: true, attempts: 2, id: Some("at1expected".to_string()), error: None });
+        assert!(!results[1].accepted);
+        assert_eq!(results[1].attempts, 1);
+        assert_eq!(results[1].id.as_deref(), Some("at1other"));
+        assert!(!results[2].accepted);
+        assert_eq!(results[2].attempts, 3);
+    }
+}
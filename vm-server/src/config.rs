@@ -0,0 +1,446 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{anyhow, ensure, Context};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use snarkvm_console_network::Network;
+use warp::http::Uri;
+use crate::auth::{ApiKey, API_KEY_HEADER, KEY_ID_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::keys::{WarmupTarget, DEFAULT_WARMUP};
+use crate::storage::StorageKind;
+use crate::REQUEST_ID_HEADER;
+
+/// The command-line flags of the server. Every flag can also be set through the environment.
+#[derive(Clone, Debug, Default, Parser)]
+#[command(name = "vm-server", version, about = "Delegated proving server for Aleo wallets")]
+pub struct Cli {
+    /// Path to a TOML configuration file.
+    #[arg(long, short, env = "VM_SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// The socket address to listen on.
+    #[arg(long, env = "VM_SERVER_BIND")]
+    pub bind: Option<SocketAddr>,
+    /// The PEM certificate chain served over TLS, with `--tls-key`.
+    #[arg(long, env = "VM_SERVER_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM private key of the TLS certificate.
+    #[arg(long, env = "VM_SERVER_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+    /// How often the TLS files are checked for changes, in seconds.
+    #[arg(long, env = "VM_SERVER_TLS_RELOAD_SECS")]
+    pub tls_reload_secs: Option<u64>,
+    /// The origins allowed by CORS (comma separated, `*` allows any origin).
+    #[arg(long = "cors-origin", env = "VM_SERVER_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Option<Vec<String>>,
+    /// The maximum request body size, in bytes.
+    #[arg(long, env = "VM_SERVER_BODY_LIMIT")]
+    pub body_limit: Option<u64>,
+    /// The base URL of the node used for state queries.
+    #[arg(long, env = "VM_SERVER_QUERY_ENDPOINT")]
+    pub query_endpoint: Option<String>,
+    /// The base URLs of the nodes queried when the query endpoint fails (comma separated).
+    #[arg(long = "query-fallback", env = "VM_SERVER_QUERY_FALLBACKS", value_delimiter = ',')]
+    pub query_fallbacks: Option<Vec<String>>,
+    /// How long the latest state root is cached, in milliseconds.
+    #[arg(long, env = "VM_SERVER_STATE_ROOT_TTL_MS")]
+    pub state_root_ttl_ms: Option<u64>,
+    /// The network to prove for.
+    #[arg(long, env = "VM_SERVER_NETWORK")]
+    pub network: Option<String>,
+    /// The number of proofs computed concurrently.
+    #[arg(long, env = "VM_SERVER_WORKERS")]
+    pub workers: Option<usize>,
+    /// The maximum number of queued and running proving jobs.
+    #[arg(long, env = "VM_SERVER_MAX_QUEUE")]
+    pub max_queue: Option<usize>,
+    /// The maximum number of requests in a `/execute_batch` call.
+    #[arg(long, env = "VM_SERVER_MAX_BATCH")]
+    pub max_batch: Option<usize>,
+    /// How long finished jobs can be polled, in seconds.
+    #[arg(long, env = "VM_SERVER_JOB_RETENTION_SECS")]
+    pub job_retention_secs: Option<u64>,
+    /// How long in-flight jobs may run after a shutdown signal, in seconds.
+    #[arg(long, env = "VM_SERVER_DRAIN_TIMEOUT_SECS")]
+    pub drain_timeout_secs: Option<u64>,
+    /// How long a proved request is remembered, in seconds.
+    #[arg(long, env = "VM_SERVER_REPLAY_WINDOW_SECS")]
+    pub replay_window_secs: Option<u64>,
+    /// Where loaded programs and circuit keys are kept.
+    #[arg(long, value_enum, env = "VM_SERVER_STORAGE")]
+    pub storage: Option<StorageKind>,
+    /// The directory of the disk storage.
+    #[arg(long, env = "VM_SERVER_STORAGE_PATH")]
+    pub storage_path: Option<PathBuf>,
+    /// The base URLs of the nodes transactions are broadcast to (comma separated).
+    #[arg(long = "broadcast-node", env = "VM_SERVER_BROADCAST_NODES", value_delimiter = ',')]
+    pub broadcast_nodes: Option<Vec<String>>,
+    /// How many times a failed broadcast to a node is retried.
+    #[arg(long, env = "VM_SERVER_BROADCAST_RETRIES")]
+    pub broadcast_retries: Option<u32>,
+    /// The delay between broadcast retries, in milliseconds.
+    #[arg(long, env = "VM_SERVER_BROADCAST_RETRY_DELAY_MS")]
+    pub broadcast_retry_delay_ms: Option<u64>,
+    /// How many blocks the global state root of a verified transaction may be behind.
+    #[arg(long, env = "VM_SERVER_STATE_ROOT_WINDOW")]
+    pub state_root_window: Option<u32>,
+    /// The minimum fee per byte of execution, in gates.
+    #[arg(long, env = "VM_SERVER_FEE_PER_BYTE")]
+    pub fee_per_byte: Option<u64>,
+    /// The programs or functions to synthesize keys for at startup (comma separated).
+    #[arg(long, env = "VM_SERVER_WARMUP", value_delimiter = ',')]
+    pub warmup: Option<Vec<String>>,
+}
+
+/// The server configuration.
+///
+/// Values are resolved from the defaults, then the TOML file, then the environment and
+/// finally the command-line flags.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The socket address to listen on.
+    pub bind: SocketAddr,
+    /// The PEM certificate chain served over TLS, plain HTTP is served if unset.
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM private key of the TLS certificate.
+    pub tls_key: Option<PathBuf>,
+    /// How often the TLS files are checked for changes, in seconds, `0` disables the reload.
+    pub tls_reload_secs: u64,
+    /// The origins allowed by CORS, `*` allows any origin.
+    pub cors_origins: Vec<String>,
+    /// The maximum request body size, in bytes.
+    pub body_limit: u64,
+    /// The base URL of the node used for state queries.
+    pub query_endpoint: String,
+    /// The base URLs of the nodes queried, in order, when the query endpoint fails.
+    pub query_fallbacks: Vec<String>,
+    /// How long the latest state root is cached, in milliseconds, `0` disables the cache.
+    pub state_root_ttl_ms: u64,
+    /// The network to prove for.
+    pub network: String,
+    /// The number of proofs computed concurrently.
+    pub workers: usize,
+    /// The maximum number of queued and running proving jobs.
+    pub max_queue: usize,
+    /// The maximum number of requests in a `/execute_batch` call, at most `max_queue`.
+    pub max_batch: usize,
+    /// How long finished jobs can be polled, in seconds.
+    pub job_retention_secs: u64,
+    /// How long in-flight jobs may run after SIGTERM or SIGINT, in seconds, before they are aborted.
+    pub drain_timeout_secs: u64,
+    /// How long a proved request is remembered, in seconds. Submitting it again returns its
+    /// transaction, and other requests spending its records are refused.
+    pub replay_window_secs: u64,
+    /// Where loaded programs and circuit keys are kept.
+    pub storage: StorageKind,
+    /// The directory of the disk storage, defaults to `~/.aleo/vm-server/{network id}`.
+    pub storage_path: Option<PathBuf>,
+    /// The base URLs of the nodes transactions are broadcast to by `/execute_and_broadcast`.
+    pub broadcast_nodes: Vec<String>,
+    /// How many times a failed broadcast to a node is retried.
+    pub broadcast_retries: u32,
+    /// The delay between broadcast retries, in milliseconds.
+    pub broadcast_retry_delay_ms: u64,
+    /// How many blocks the global state root of a verified transaction may be behind.
+    pub state_root_window: u32,
+    /// The minimum fee per byte of execution, in gates, `0` accepts any fee.
+    pub fee_per_byte: u64,
+    /// The clients allowed to use the server, anyone can use it if empty. Only set in the file.
+    pub api_keys: Vec<ApiKey>,
+    /// The programs (`program.aleo`) or functions (`program.aleo/function`) to synthesize keys
+    /// for at startup, in addition to `credits.aleo/transfer` and `credits.aleo/fee`.
+    pub warmup: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 17777)),
+            tls_cert: None,
+            tls_key: None,
+            tls_reload_secs: 30,
+            cors_origins: vec!["*".to_string()],
+            body_limit: 16 * 1024 * 1024,
+            query_endpoint: "https://vm.aleo.org/api".to_string(),
+            query_fallbacks: vec![],
+            state_root_ttl_ms: 2000,
+            network: "testnet3".to_string(),
+            workers: 2,
+            max_queue: 32,
+            max_batch: 16,
+            job_retention_secs: 600,
+            drain_timeout_secs: 120,
+            replay_window_secs: 600,
+            storage: StorageKind::Memory,
+            storage_path: None,
+            broadcast_nodes: vec![],
+            broadcast_retries: 3,
+            broadcast_retry_delay_ms: 500,
+            state_root_window: 10,
+            fee_per_byte: 1,
+            api_keys: vec![],
+            warmup: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Resolves the configuration for the given flags and validates it for the network `N`.
+    pub fn load<N: Network>(cli: Cli) -> anyhow::Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply(cli);
+        config.validate::<N>()?;
+        Ok(config)
+    }
+
+    /// Reads the configuration from the TOML file at the given path.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file '{}'", path.display()))?;
+        Self::from_str(&contents).with_context(|| format!("failed to parse config file '{}'", path.display()))
+    }
+
+    /// Overrides the configuration with the flags (and environment variables) that are set.
+    pub fn apply(&mut self, cli: Cli) {
+        if let Some(bind) = cli.bind {
+            self.bind = bind;
+        }
+        if let Some(tls_cert) = cli.tls_cert {
+            self.tls_cert = Some(tls_cert);
+        }
+        if let Some(tls_key) = cli.tls_key {
+            self.tls_key = Some(tls_key);
+        }
+        if let Some(tls_reload_secs) = cli.tls_reload_secs {
+            self.tls_reload_secs = tls_reload_secs;
+        }
+        if let Some(cors_origins) = cli.cors_origins {
+            self.cors_origins = cors_origins;
+        }
+        if let Some(body_limit) = cli.body_limit {
+            self.body_limit = body_limit;
+        }
+        if let Some(query_endpoint) = cli.query_endpoint {
+            self.query_endpoint = query_endpoint;
+        }
+        if let Some(query_fallbacks) = cli.query_fallbacks {
+            self.query_fallbacks = query_fallbacks;
+        }
+        if let Some(state_root_ttl_ms) = cli.state_root_ttl_ms {
+            self.state_root_ttl_ms = state_root_ttl_ms;
+        }
+        if let Some(network) = cli.network {
+            self.network = network;
+        }
+        if let Some(workers) = cli.workers {
+            self.workers = workers;
+        }
+        if let Some(max_queue) = cli.max_queue {
+            self.max_queue = max_queue;
+        }
+        if let Some(max_batch) = cli.max_batch {
+            self.max_batch = max_batch;
+        }
+        if let Some(job_retention_secs) = cli.job_retention_secs {
+            self.job_retention_secs = job_retention_secs;
+        }
+        if let Some(drain_timeout_secs) = cli.drain_timeout_secs {
+            self.drain_timeout_secs = drain_timeout_secs;
+        }
+        if let Some(replay_window_secs) = cli.replay_window_secs {
+            self.replay_window_secs = replay_window_secs;
+        }
+        if let Some(storage) = cli.storage {
+            self.storage = storage;
+        }
+        if let Some(storage_path) = cli.storage_path {
+            self.storage_path = Some(storage_path);
+        }
+        if let Some(broadcast_nodes) = cli.broadcast_nodes {
+            self.broadcast_nodes = broadcast_nodes;
+        }
+        if let Some(broadcast_retries) = cli.broadcast_retries {
+            self.broadcast_retries = broadcast_retries;
+        }
+        if let Some(broadcast_retry_delay_ms) = cli.broadcast_retry_delay_ms {
+            self.broadcast_retry_delay_ms = broadcast_retry_delay_ms;
+        }
+        if let Some(state_root_window) = cli.state_root_window {
+            self.state_root_window = state_root_window;
+        }
+        if let Some(fee_per_byte) = cli.fee_per_byte {
+            self.fee_per_byte = fee_per_byte;
+        }
+        if let Some(warmup) = cli.warmup {
+            self.warmup = warmup;
+        }
+    }
+
+    /// Ensures the configuration is usable for the network `N`.
+    pub fn validate<N: Network>(&mut self) -> anyhow::Result<()> {
+        ensure!(self.body_limit > 0, "body_limit must be greater than zero");
+        ensure!(self.tls_cert.is_some() == self.tls_key.is_some(), "tls_cert and tls_key must be set together");
+        ensure!(self.workers > 0, "workers must be greater than zero");
+        ensure!(self.max_queue >= self.workers, "max_queue must be at least the number of workers");
+        ensure!(self.max_batch > 0 && self.max_batch <= self.max_queue, "max_batch must be between 1 and max_queue");
+        ensure!(
+            cfg!(feature = "disk") || self.storage != StorageKind::Disk,
+            "the disk storage requires building vm-server with the `disk` feature"
+        );
+
+        let expected = network_name::<N>()?;
+        ensure!(
+            self.network == expected,
+            "network '{}' is not supported, this server is built for '{}'",
+            self.network,
+            expected
+        );
+
+        self.query_endpoint = http_url("query_endpoint", &self.query_endpoint)?;
+        self.query_fallbacks = self.query_fallbacks.iter().map(|node| http_url("query fallback", node)).collect::<anyhow::Result<_>>()?;
+        self.broadcast_nodes = self.broadcast_nodes.iter().map(|node| http_url("broadcast node", node)).collect::<anyhow::Result<_>>()?;
+
+        ensure!(!self.cors_origins.is_empty(), "cors_origins must not be empty, use '*' to allow any origin");
+        for origin in self.cors_origins.iter().filter(|origin| origin.as_str() != "*") {
+            let uri = Uri::from_str(origin).map_err(|e| anyhow!("CORS origin '{}' is invalid: {}", origin, e))?;
+            ensure!(
+                uri.scheme().is_some() && uri.authority().is_some() && matches!(uri.path(), "" | "/"),
+                "CORS origin '{}' must be of the form 'scheme://host[:port]'",
+                origin
+            );
+        }
+
+        for (index, api_key) in self.api_keys.iter().enumerate() {
+            ensure!(!api_key.name.is_empty() && !api_key.key.is_empty(), "API keys must have a name and a key");
+            ensure!(
+                self.api_keys[..index].iter().all(|other| other.name != api_key.name && other.key != api_key.key),
+                "API key '{}' is not unique",
+                api_key.name
+            );
+        }
+        self.warmup_targets::<N>()?;
+
+        Ok(())
+    }
+
+    /// Returns the functions to warm up at startup, starting with the default `credits.aleo` ones.
+    pub fn warmup_targets<N: Network>(&self) -> anyhow::Result<Vec<WarmupTarget<N>>> {
+        let mut targets = Vec::new();
+        for target in DEFAULT_WARMUP.iter().copied().chain(self.warmup.iter().map(String::as_str)) {
+            let target = WarmupTarget::from_str(target)?;
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        Ok(targets)
+    }
+
+    /// Returns the nodes queried for the chain state, the query endpoint first.
+    pub fn query_endpoints(&self) -> Vec<String> {
+        std::iter::once(&self.query_endpoint).chain(&self.query_fallbacks).cloned().collect()
+    }
+
+    /// Returns the CORS filter for the configured origins.
+    pub fn cors(&self) -> warp::cors::Builder {
+        let cors = warp::cors()
+            .allow_headers(["content-type", REQUEST_ID_HEADER, API_KEY_HEADER, KEY_ID_HEADER, TIMESTAMP_HEADER, SIGNATURE_HEADER])
+            .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS"]);
+        if self.cors_origins.iter().any(|origin| origin == "*") {
+            cors.allow_any_origin()
+        } else {
+            cors.allow_origins(self.cors_origins.iter().map(|origin| origin.trim_end_matches('/')))
+        }
+    }
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+/// Ensures the URL is an http(s) URL, and returns it without a trailing slash.
+fn http_url(name: &str, url: &str) -> anyhow::Result<String> {
+    let url = url.trim_end_matches('/');
+    let uri = Uri::from_str(url).map_err(|e| anyhow!("{} '{}' is not a valid URL: {}", name, url, e))?;
+    ensure!(
+        matches!(uri.scheme_str(), Some("http") | Some("https")) && uri.authority().is_some(),
+        "{} '{}' must be an http(s) URL",
+        name,
+        url
+    );
+    Ok(url.to_string())
+}
+
+/// Returns the name used by the query endpoint for the network `N`.
+pub fn network_name<N: Network>() -> anyhow::Result<&'static str> {
+    vm_protocol::network_name::<N>().ok_or_else(|| anyhow!("unsupported network ID {}", N::ID))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CurrentNetwork;
+
+    #[test]
+    fn test_config_from_toml() {
+        let config = Config::from_str(
+            r#"
+            bind = "127.0.0.1:8080"
+            cors_origins = ["https://wallet.example.com"]
+            query_endpoint = "https://node.example.com/api/"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.bind, SocketAddr::from(([127, 0, 0, 1], 8080)));
+        assert_eq!(config.body_limit, Config::default().body_limit);
+        assert!(Config::from_str("port = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_flags_override_file() {
+        let mut config = Config::from_str(r#"query_endpoint = "http://file:3030""#).unwrap();
+        config.apply(Cli {
+            query_endpoint: Some("http://flag:3030/".to_string()),
+            query_fallbacks: Some(vec!["http://fallback:3030/".to_string()]),
+            ..Default::default()
+        });
+        config.validate::<CurrentNetwork>().unwrap();
+        assert_eq!(config.query_endpoint, "http://flag:3030");
+        assert_eq!(config.query_endpoints(), ["http://flag:3030", "http://fallback:3030"]);
+    }
+
+    fn api_key(name: &str, key: &str) -> ApiKey {
+        ApiKey { name: name.to_string(), key: key.to_string(), requests_per_minute: None, proofs_per_day: None }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let invalid = [
+            Config { network: "mainnet".to_string(), ..Default::default() },
+            Config { query_endpoint: "vm.aleo.org/api".to_string(), ..Default::default() },
+            Config { cors_origins: vec!["https://wallet.example.com/app".to_string()], ..Default::default() },
+            Config { cors_origins: vec![], ..Default::default() },
+            Config { body_limit: 0, ..Default::default() },
+            Config { tls_cert: Some(PathBuf::from("cert.pem")), ..Default::default() },
+            Config { workers: 0, ..Default::default() },
+            Config { workers: 4, max_queue: 2, ..Default::default() },
+            Config { max_batch: 0, ..Default::default() },
+            Config { max_batch: 64, ..Default::default() },
+            Config { warmup: vec!["token".to_string()], ..Default::default() },
+            Config { broadcast_nodes: vec!["node.example.com:3033".to_string()], ..Default::default() },
+            Config { query_fallbacks: vec!["ftp://node.example.com".to_string()], ..Default::default() },
+            Config { api_keys: vec![api_key("wallet", "secret"), api_key("wallet", "other")], ..Default::default() },
+        ];
+        for mut config in invalid {
+            assert!(config.validate::<CurrentNetwork>().is_err(), "{:?}", config);
+        }
+        Config::default().validate::<CurrentNetwork>().unwrap();
+    }
+}
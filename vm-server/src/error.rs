@@ -0,0 +1,216 @@
+use std::convert::Infallible;
+use std::fmt;
+use vm_protocol::ProtocolError;
+use warp::http::StatusCode;
+use warp::{reject, Rejection, Reply};
+
+pub use vm_protocol::ErrorResponse;
+
+/// An enum of error handlers for the REST API server.
+#[derive(Clone, Debug)]
+pub enum RestError {
+    /// The request body could not be parsed.
+    BadRequest(String),
+    /// The request is for another network.
+    WrongNetwork(String),
+    /// The client speaks another protocol version.
+    UnsupportedVersion(String),
+    /// The request has no valid API key or signature.
+    Unauthorized(String),
+    /// The client sent too many requests.
+    RateLimited(String),
+    /// The client used up its proving quota.
+    QuotaExceeded(String),
+    /// The requested program is not loaded.
+    UnknownProgram(String),
+    /// A record in the request is malformed.
+    InvalidRecord(String),
+    /// The signed request was rejected.
+    Authorization(String),
+    /// The fee does not match the fee record or is too low.
+    Fee(String),
+    /// The request needs a feature the server does not support.
+    Unsupported(String),
+    /// The transaction failed verification.
+    Verification(String),
+    /// Proving the execution or the fee failed.
+    Proving(String),
+    /// The upstream query endpoint could not be reached.
+    Query(String),
+    /// No node accepted the transaction.
+    Broadcast(String),
+    /// The proving queue is full.
+    QueueFull(String),
+    /// The requested job does not exist.
+    UnknownJob(String),
+    /// The job was cancelled before it produced a transaction.
+    Cancelled(String),
+    /// The same signed request is already being proved.
+    Duplicate(String),
+    /// A record of the request is spent by another request.
+    Conflict(String),
+    /// The server is still warming up.
+    NotReady(String),
+    /// The server is shutting down and takes no new work.
+    Draining(String),
+}
+
+impl RestError {
+    /// Classifies an error raised while proving, separating failures of the upstream query endpoint.
+    pub fn from_proving(error: anyhow::Error) -> Self {
+        let is_query = error.chain().any(|cause| {
+            cause.downcast_ref::<ureq::Error>().is_some() || cause.to_string().starts_with("Failed to fetch from")
+        });
+        match is_query {
+            true => RestError::Query(format!("{:#}", error)),
+            false => RestError::Proving(format!("{:#}", error)),
+        }
+    }
+
+    /// Returns the error a rejection carries, for handlers reporting errors in their own response.
+    pub fn from_rejection(rejection: Rejection) -> Self {
+        match rejection.find::<RestError>() {
+            Some(error) => error.clone(),
+            None => RestError::BadRequest(format!("{:?}", rejection)),
+        }
+    }
+
+    /// Returns the HTTP status code of the error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RestError::BadRequest(_) | RestError::WrongNetwork(_) | RestError::UnsupportedVersion(_) => StatusCode::BAD_REQUEST,
+            RestError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            RestError::RateLimited(_) | RestError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            RestError::UnknownProgram(_) => StatusCode::NOT_FOUND,
+            RestError::InvalidRecord(_) | RestError::Authorization(_) | RestError::Fee(_) | RestError::Verification(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            RestError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            RestError::Proving(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            RestError::Query(_) | RestError::Broadcast(_) => StatusCode::BAD_GATEWAY,
+            RestError::QueueFull(_) | RestError::NotReady(_) | RestError::Draining(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RestError::UnknownJob(_) => StatusCode::NOT_FOUND,
+            RestError::Cancelled(_) | RestError::Duplicate(_) | RestError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    /// Returns the JSON body of the error.
+    pub fn to_response(&self) -> ErrorResponse {
+        let (code, message, details) = match self {
+            RestError::BadRequest(details) => ("bad_request", "the request body is invalid", details),
+            RestError::WrongNetwork(details) => ("wrong_network", "the request is for another network", details),
+            RestError::UnsupportedVersion(details) => ("unsupported_version", "the client protocol version is not supported", details),
+            RestError::Unauthorized(details) => ("unauthorized", "the request is not authenticated", details),
+            RestError::RateLimited(details) => ("rate_limited", "too many requests, retry later", details),
+            RestError::QuotaExceeded(details) => ("quota_exceeded", "the proving quota is used up", details),
+            RestError::UnknownProgram(details) => ("unknown_program", "the program is not loaded", details),
+            RestError::InvalidRecord(details) => ("invalid_record", "a record in the request is invalid", details),
+            RestError::Authorization(details) => ("authorization_failed", "the signed request was rejected", details),
+            RestError::Fee(details) => ("invalid_fee", "the fee is invalid", details),
+            RestError::Unsupported(details) => ("unsupported", "the request is not supported", details),
+            RestError::Verification(details) => ("verification_failed", "the transaction is invalid", details),
+            RestError::Proving(details) => ("proving_failed", "failed to prove the transaction", details),
+            RestError::Query(details) => ("query_failed", "the query endpoint could not be reached", details),
+            RestError::Broadcast(details) => ("broadcast_failed", "no node accepted the transaction", details),
+            RestError::QueueFull(details) => ("queue_full", "the proving queue is full, retry later", details),
+            RestError::UnknownJob(details) => ("unknown_job", "the job does not exist", details),
+            RestError::Cancelled(details) => ("cancelled", "the job was cancelled", details),
+            RestError::Duplicate(details) => ("duplicate_request", "the request is already being proved", details),
+            RestError::Conflict(details) => ("conflicting_request", "a record of the request is spent by another request", details),
+            RestError::NotReady(details) => ("not_ready", "the server is warming up", details),
+            RestError::Draining(details) => ("draining", "the server is shutting down, retry on another server", details),
+        };
+        ErrorResponse { code: code.to_string(), message: message.to_string(), details: Some(details.clone()) }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let response = self.to_response();
+        write!(f, "{}: {}", response.message, response.details.unwrap_or_default())
+    }
+}
+
+impl std::error::Error for RestError {}
+
+impl warp::reject::Reject for RestError {}
+
+impl From<ProtocolError> for RestError {
+    fn from(error: ProtocolError) -> Self {
+        match error {
+            ProtocolError::WrongNetwork { .. } => RestError::WrongNetwork(error.to_string()),
+            ProtocolError::UnsupportedVersion { .. } => RestError::UnsupportedVersion(error.to_string()),
+        }
+    }
+}
+
+/// A trait to unwrap a `Result` or `Reject`.
+pub trait OrReject<T> {
+    /// Returns the result if it is successful, otherwise returns a rejection of the given kind.
+    fn or_reject(self, error: fn(String) -> RestError) -> Result<T, Rejection>;
+}
+
+impl<T> OrReject<T> for anyhow::Result<T> {
+    /// Returns the result if it is successful, otherwise returns a rejection of the given kind.
+    fn or_reject(self, error: fn(String) -> RestError) -> Result<T, Rejection> {
+        self.map_err(|e| reject::custom(error(format!("{:#}", e))))
+    }
+}
+
+/// Converts a rejection into a JSON error response.
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let (status, response) = if let Some(error) = rejection.find::<RestError>() {
+        (error.status(), error.to_response())
+    } else if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, response("not_found", "the route does not exist", None))
+    } else if let Some(error) = rejection.find::<warp::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, RestError::BadRequest(error.to_string()).to_response())
+    } else if rejection.find::<reject::PayloadTooLarge>().is_some() {
+        (StatusCode::PAYLOAD_TOO_LARGE, response("payload_too_large", "the request body is too large", None))
+    } else if rejection.find::<reject::LengthRequired>().is_some() {
+        (StatusCode::LENGTH_REQUIRED, response("length_required", "the request body has no content length", None))
+    } else if let Some(error) = rejection.find::<reject::UnsupportedMediaType>() {
+        (StatusCode::UNSUPPORTED_MEDIA_TYPE, response("unsupported_media_type", "the request body must be JSON", Some(error.to_string())))
+    } else if rejection.find::<reject::MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, response("method_not_allowed", "the method is not allowed", None))
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, response("internal", "unhandled rejection", Some(format!("{:?}", rejection))))
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+/// Builds an error response that has no matching `RestError`.
+fn response(code: &str, message: &str, details: Option<String>) -> ErrorResponse {
+    ErrorResponse { code: code.to_string(), message: message.to_string(), details }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Filter;
+
+    #[test]
+    fn test_proving_errors_are_classified() {
+        let query = anyhow::anyhow!("Failed to fetch from http://127.0.0.1:3030/testnet3/latest/stateRoot");
+        assert!(matches!(RestError::from_proving(query.context("prepare the assignments")), RestError::Query(_)));
+        let proving = anyhow::anyhow!("Request is invalid");
+        assert!(matches!(RestError::from_proving(proving), RestError::Proving(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rejections_are_json() {
+        let filter = warp::path!("program")
+            .and_then(|| async { Err::<String, _>(reject::custom(RestError::UnknownProgram("foo.aleo".to_string()))) })
+            .recover(handle_rejection);
+
+        let reply = warp::test::request().path("/program").reply(&filter).await;
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        let body = serde_json::from_slice::<ErrorResponse>(reply.body()).unwrap();
+        assert_eq!(body.code, "unknown_program");
+        assert_eq!(body.details.as_deref(), Some("foo.aleo"));
+
+        let reply = warp::test::request().path("/missing").reply(&filter).await;
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        assert_eq!(serde_json::from_slice::<ErrorResponse>(reply.body()).unwrap().code, "not_found");
+    }
+}
@@ -0,0 +1,188 @@
+use snarkvm_console_network::prelude::ToBytes;
+use snarkvm_console_network::Network;
+use snarkvm_console_program::{Literal, Plaintext, Record, Request, Response, Value};
+use snarkvm_synthesizer::{Execution, Fee, Program};
+use vm_protocol::{FeeEstimate, FeeKind};
+use crate::error::RestError;
+
+/// The estimated size of a transition besides its inputs and outputs: its IDs, keys and proof.
+const TRANSITION_BASE_SIZE: u64 = 1024;
+
+/// The estimated size of a `credits.aleo/fee` transition: a record in, a record out and the amount.
+const FEE_TRANSITION_SIZE: u64 = TRANSITION_BASE_SIZE + 512;
+
+/// The estimated size of the verifying key and certificate of each function of a deployment.
+const DEPLOYMENT_FUNCTION_SIZE: u64 = 1024;
+
+/// Returns the fee estimate of an execution of the given size, instructions and constraints.
+pub fn fee_estimate(execution_size: u64, num_instructions: usize, num_constraints: usize, fee_per_byte: u64) -> FeeEstimate {
+    FeeEstimate {
+        execution_size,
+        num_instructions,
+        num_constraints,
+        minimum_fee: minimum_fee(execution_size, fee_per_byte),
+        recommended_fee: minimum_fee(execution_size.saturating_add(FEE_TRANSITION_SIZE), fee_per_byte),
+    }
+}
+
+/// Estimates the size of the execution of the requests, in bytes, from their inputs and the
+/// outputs of the function. Each request is a transition.
+pub fn estimate_execution_size<N: Network>(requests: &[Request<N>], response: &Response<N>) -> u64 {
+    let values = requests.iter().flat_map(|request| request.inputs()).chain(response.outputs());
+    let values_size = values.map(|value| value.to_bytes_le().map_or(0, |bytes| bytes.len() as u64)).sum::<u64>();
+    TRANSITION_BASE_SIZE * requests.len().max(1) as u64 + values_size
+}
+
+/// Estimates the size of the deployment of the program, in bytes.
+pub fn estimate_deployment_size<N: Network>(program: &Program<N>) -> u64 {
+    let program_size = program.to_bytes_le().map_or(0, |bytes| bytes.len() as u64);
+    program_size + program.functions().len() as u64 * DEPLOYMENT_FUNCTION_SIZE
+}
+
+/// Returns the size of the proved execution, in bytes.
+pub fn execution_size<N: Network>(execution: &Execution<N>) -> u64 {
+    execution.to_bytes_le().map_or(0, |bytes| bytes.len() as u64)
+}
+
+/// Returns the minimum fee, in gates, of an execution of the given size.
+pub fn minimum_fee(execution_size: u64, fee_per_byte: u64) -> u64 {
+    execution_size.saturating_mul(fee_per_byte)
+}
+
+/// Ensures fees of the given kind can be proved.
+///
+/// The `credits.aleo` of snarkVM 0.9.16 has neither `fee_public` nor the `account` mapping, so
+/// only private fees are supported until the server moves to a snarkVM release providing them.
+pub fn check_fee_kind(kind: FeeKind) -> Result<(), RestError> {
+    match kind {
+        FeeKind::Private => Ok(()),
+        FeeKind::Public => Err(RestError::Unsupported(
+            "public fees need 'credits.aleo/fee_public' and the 'account' mapping, which this network does not provide; pay the fee from a record".to_string(),
+        )),
+    }
+}
+
+/// Checks the fee request pays `declared` gates (if set) from the fee record, and that the
+/// record covers it and the fee reaches the `minimum`. Returns the fee amount.
+pub fn check_fee<N: Network>(
+    fee_request: &Request<N>,
+    fee_record: &Record<N, Plaintext<N>>,
+    declared: Option<u64>,
+    minimum: u64,
+) -> Result<u64, RestError> {
+    if fee_request.program_id().to_string() != "credits.aleo" || fee_request.function_name().to_string() != "fee" {
+        return Err(RestError::Fee(format!(
+            "the fee request calls '{}/{}' instead of 'credits.aleo/fee'",
+            fee_request.program_id(),
+            fee_request.function_name()
+        )));
+    }
+    let (record, amount) = match fee_request.inputs() {
+        [Value::Record(record), Value::Plaintext(Plaintext::Literal(Literal::U64(amount), _))] => (record, **amount),
+        _ => return Err(RestError::Fee("the fee request must take a record and a u64 amount".to_string())),
+    };
+
+    if record != fee_record {
+        return Err(RestError::Fee("the fee request does not consume the fee record".to_string()));
+    }
+    if let Some(declared) = declared {
+        if declared != amount {
+            return Err(RestError::Fee(format!("the fee request pays {} gates, but the declared fee is {} gates", amount, declared)));
+        }
+    }
+    let balance = ***record.gates();
+    if balance < amount {
+        return Err(RestError::Fee(format!("the fee record holds {} gates, the fee is {} gates", balance, amount)));
+    }
+    if amount < minimum {
+        return Err(RestError::Fee(format!("the fee is {} gates, the minimum for this execution is {} gates", amount, minimum)));
+    }
+    Ok(amount)
+}
+
+/// Checks the fee, proved on its own, covers the execution it is assembled with. Returns the fee amount.
+pub fn check_fee_covers<N: Network>(execution: &Execution<N>, fee: &Fee<N>, fee_per_byte: u64) -> Result<u64, RestError> {
+    let amount = u64::try_from(*fee.fee()).map_err(|_| RestError::Fee(format!("the fee transition pays {} gates", fee.fee())))?;
+    let minimum = minimum_fee(execution_size(execution), fee_per_byte);
+    if amount < minimum {
+        return Err(RestError::Fee(format!("the fee is {} gates, the minimum for this execution is {} gates", amount, minimum)));
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use snarkvm_console_account::{Address, PrivateKey};
+    use snarkvm_console_program::Identifier;
+    use crate::CurrentNetwork;
+
+    fn record(owner: &Address<CurrentNetwork>, gates: u64) -> Record<CurrentNetwork, Plaintext<CurrentNetwork>> {
+        Record::from_str(&format!("{{ owner: {}.private, gates: {}u64.private, _nonce: 0group.public }}", owner, gates)).unwrap()
+    }
+
+    fn sign_fee(
+        private_key: &PrivateKey<CurrentNetwork>,
+        record: &Record<CurrentNetwork, Plaintext<CurrentNetwork>>,
+        amount: u64,
+    ) -> Request<CurrentNetwork> {
+        let program = Program::<CurrentNetwork>::credits().unwrap();
+        let function_name = Identifier::from_str("fee").unwrap();
+        let inputs = [Value::Record(record.clone()), Value::from_str(&format!("{}u64", amount)).unwrap()];
+        let input_types = program.get_function(&function_name).unwrap().input_types();
+        Request::sign(private_key, *program.id(), function_name, inputs.iter(), &input_types, &mut rand::thread_rng()).unwrap()
+    }
+
+    #[test]
+    fn test_check_fee() {
+        let private_key = PrivateKey::<CurrentNetwork>::new(&mut rand::thread_rng()).unwrap();
+        let owner = Address::try_from(&private_key).unwrap();
+        let fee_record = record(&owner, 1_000);
+        let fee_request = sign_fee(&private_key, &fee_record, 600);
+
+        assert_eq!(check_fee(&fee_request, &fee_record, Some(600), 500).unwrap(), 600);
+        assert_eq!(check_fee(&fee_request, &fee_record, None, 0).unwrap(), 600);
+        // The declared fee differs from the signed one.
+        assert!(matches!(check_fee(&fee_request, &fee_record, Some(700), 0), Err(RestError::Fee(_))));
+        // The fee is below the minimum.
+        assert!(matches!(check_fee(&fee_request, &fee_record, None, 601), Err(RestError::Fee(_))));
+        // The fee request spends another record.
+        assert!(matches!(check_fee(&fee_request, &record(&owner, 2_000), None, 0), Err(RestError::Fee(_))));
+        // The record does not cover the fee.
+        let small_record = record(&owner, 100);
+        let fee_request = sign_fee(&private_key, &small_record, 600);
+        assert!(matches!(check_fee(&fee_request, &small_record, None, 0), Err(RestError::Fee(_))));
+    }
+
+    #[test]
+    fn test_fee_kind() {
+        #[derive(serde::Deserialize)]
+        struct Body {
+            #[serde(default)]
+            fee_kind: FeeKind,
+        }
+        assert_eq!(serde_json::from_str::<Body>("{}").unwrap().fee_kind, FeeKind::Private);
+        assert_eq!(serde_json::from_str::<Body>(r#"{ "fee_kind": "public" }"#).unwrap().fee_kind, FeeKind::Public);
+        assert!(serde_json::from_str::<Body>(r#"{ "fee_kind": "gift" }"#).is_err());
+
+        assert!(check_fee_kind(FeeKind::Private).is_ok());
+        assert!(matches!(check_fee_kind(FeeKind::Public), Err(RestError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_minimum_fee() {
+        assert_eq!(minimum_fee(2_000, 0), 0);
+        assert_eq!(minimum_fee(2_000, 3), 6_000);
+        assert_eq!(minimum_fee(u64::MAX, 2), u64::MAX);
+
+        let estimate = fee_estimate(2_000, 4, 60_000, 2);
+        assert_eq!(estimate.minimum_fee, 4_000);
+        assert!(estimate.recommended_fee > estimate.minimum_fee);
+        // The default configuration recommends a fee.
+        assert!(fee_estimate(2_000, 4, 60_000, crate::config::Config::default().fee_per_byte).recommended_fee > 0);
+
+        let program = Program::<CurrentNetwork>::credits().unwrap();
+        assert!(estimate_deployment_size(&program) > program.functions().len() as u64 * DEPLOYMENT_FUNCTION_SIZE);
+    }
+}
@@ -0,0 +1,274 @@
+//! An in-process harness booting the routes of the server against a local stub node, so the
+//! proving paths are tested without a live node.
+//!
+//! The proving tests are ignored, run them with `cargo test --all-features -- --include-ignored`.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use circuit::AleoV0;
+use parking_lot::Mutex;
+use snarkvm_console_account::{PrivateKey, ViewKey};
+use snarkvm_console_network::prelude::{CryptoRng, Rng};
+use snarkvm_console_program::{Field, Identifier, Plaintext, ProgramID, Record, Request, Value};
+use snarkvm_synthesizer::{Block, ConsensusMemory, ConsensusStorage, ConsensusStore, Program, Query, Transaction, VM};
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Reply};
+use crate::auth::Auth;
+use crate::config::Config;
+use crate::error::handle_rejection;
+use crate::jobs::Jobs;
+use crate::keys::KeyCache;
+use crate::programs::Programs;
+use crate::query::{QueryProvider, RestBackend};
+use crate::replays::Replays;
+use crate::storage::{Storage, StorageKind};
+use crate::{routes, CurrentNetwork};
+
+type Ledger = VM<CurrentNetwork, ConsensusMemory<CurrentNetwork>>;
+type Blocks = <ConsensusMemory<CurrentNetwork> as ConsensusStorage<CurrentNetwork>>::BlockStorage;
+
+/// A node serving the state of a local ledger on the routes the server queries, and recording
+/// the transactions broadcast to it.
+#[derive(Clone)]
+pub struct StubNode {
+    pub url: String,
+    /// The ledger of the node, a node without one has no block, state or program.
+    ledger: Option<Ledger>,
+    blocks: Arc<Mutex<Vec<Block<CurrentNetwork>>>>,
+    pub broadcasts: Arc<Mutex<Vec<Transaction<CurrentNetwork>>>>,
+}
+
+impl StubNode {
+    /// Starts a node whose ledger holds the given blocks, genesis first.
+    pub fn start(ledger: Ledger, blocks: Vec<Block<CurrentNetwork>>) -> Self {
+        Self::serve(Some(ledger), blocks)
+    }
+
+    /// Starts a node without a ledger, it needs no proving parameters.
+    pub fn empty() -> Self {
+        Self::serve(None, vec![])
+    }
+
+    fn serve(ledger: Option<Ledger>, blocks: Vec<Block<CurrentNetwork>>) -> Self {
+        let mut node = Self { url: String::new(), ledger, blocks: Arc::new(Mutex::new(blocks)), broadcasts: Default::default() };
+        let (addr, server) = warp::serve(node.routes()).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        node.url = format!("http://{}", addr);
+        node
+    }
+
+    /// Starts a node holding a genesis block, and returns the key of the account it funds.
+    pub fn genesis<R: Rng + CryptoRng>(rng: &mut R) -> (Self, PrivateKey<CurrentNetwork>) {
+        let private_key = PrivateKey::new(rng).unwrap();
+        let ledger = Ledger::from(ConsensusStore::open(None).unwrap()).unwrap();
+        let genesis = Block::genesis(&ledger, &private_key, rng).unwrap();
+        ledger.add_next_block(&genesis).unwrap();
+        (Self::start(ledger, vec![genesis]), private_key)
+    }
+
+    /// Returns the records of the account in the ledger.
+    pub fn records(&self, private_key: &PrivateKey<CurrentNetwork>) -> Vec<Record<CurrentNetwork, Plaintext<CurrentNetwork>>> {
+        let view_key = ViewKey::try_from(private_key).unwrap();
+        let blocks = self.blocks.lock();
+        blocks.iter().flat_map(|block| block.records().filter_map(|(_, record)| record.decrypt(&view_key).ok()).collect::<Vec<_>>()).collect()
+    }
+
+    fn routes(&self) -> BoxedFilter<(warp::reply::Response,)> {
+        let node = self.clone();
+        let with_node = warp::any().map(move || node.clone());
+        let query = |node: &StubNode| node.ledger.as_ref().map(|ledger| Query::<CurrentNetwork, Blocks>::VM(ledger.block_store().clone()));
+
+        let latest_height = warp::path!("latest" / "height").and(with_node.clone()).map(|node: StubNode| {
+            match node.blocks.lock().last() {
+                Some(block) => warp::reply::json(&block.height()).into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        });
+        let latest_state_root = warp::path!("latest" / "stateRoot")
+            .and(with_node.clone())
+            .map(move |node: StubNode| match query(&node) {
+                Some(query) => warp::reply::json(&query.current_state_root().unwrap()).into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            });
+        let block = warp::path!("block" / usize).and(with_node.clone()).map(|height: usize, node: StubNode| {
+            match node.blocks.lock().get(height) {
+                Some(block) => warp::reply::json(block).into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        });
+        let state_path = warp::path!("statePath" / String).and(with_node.clone()).map(move |commitment: String, node: StubNode| {
+            let commitment = Field::<CurrentNetwork>::from_str(&commitment).unwrap();
+            match query(&node).map(|query| query.get_state_path_for_commitment(&commitment)) {
+                Some(Ok(state_path)) => warp::reply::json(&state_path).into_response(),
+                _ => StatusCode::NOT_FOUND.into_response(),
+            }
+        });
+        let program = warp::path!("program" / String).and(with_node.clone()).map(|program_id: String, node: StubNode| {
+            let program_id = program_id.parse::<ProgramID<CurrentNetwork>>().unwrap();
+            match node.ledger.as_ref().map(|ledger| ledger.process().read().get_program(&program_id).cloned()) {
+                Some(Ok(program)) => warp::reply::json(&program).into_response(),
+                _ => StatusCode::NOT_FOUND.into_response(),
+            }
+        });
+        let broadcast = warp::post()
+            .and(warp::path!("transaction" / "broadcast"))
+            .and(warp::body::json())
+            .and(with_node)
+            .map(|transaction: Transaction<CurrentNetwork>, node: StubNode| {
+                let id = transaction.id().to_string();
+                node.broadcasts.lock().push(transaction);
+                warp::reply::json(&id).into_response()
+            });
+
+        let queries = warp::get().and(latest_height.or(latest_state_root).unify().or(block).unify().or(state_path).unify().or(program).unify());
+        warp::path("testnet3").and(queries.or(broadcast).unify()).boxed()
+    }
+}
+
+/// The routes of the server, querying and broadcasting to a stub node.
+pub struct Harness {
+    pub node: StubNode,
+    routes: BoxedFilter<(warp::reply::Response,)>,
+}
+
+impl Harness {
+    /// Boots the routes with the default configuration, against the node.
+    pub fn start(node: StubNode) -> Self {
+        let config = Arc::new(Config { query_endpoint: node.url.clone(), broadcast_nodes: vec![node.url.clone()], ..Default::default() });
+        let vm = VM::from(ConsensusStore::<CurrentNetwork, ConsensusMemory<CurrentNetwork>>::open(None).unwrap()).unwrap();
+        let backend = RestBackend::<CurrentNetwork>::new(config.query_endpoints()).unwrap();
+        let query = QueryProvider::new(Arc::new(backend), Duration::from_millis(config.state_root_ttl_ms)).unwrap();
+        let programs = Programs::new(vm, query, Storage::open(StorageKind::Memory, None).unwrap(), vec![]);
+        let jobs = Jobs::new(config.workers, config.max_queue, Duration::from_secs(config.job_retention_secs));
+        let replays = Replays::new(Duration::from_secs(config.replay_window_secs));
+        let readiness = KeyCache::new(programs.clone()).readiness();
+        let routes = routes::<CurrentNetwork, AleoV0, ConsensusMemory<CurrentNetwork>>(programs, config, jobs, replays, readiness, Auth::new(vec![]));
+        Self { node, routes: routes.recover(handle_rejection).map(Reply::into_response).boxed() }
+    }
+
+    /// Sends a request to the server and returns the status and body of the response.
+    pub async fn request(&self, method: &str, path: &str, body: impl serde::Serialize) -> (StatusCode, Bytes) {
+        let request = warp::test::request().method(method).path(path);
+        let response = match method {
+            "GET" | "DELETE" => request.reply(&self.routes).await,
+            _ => request.json(&body).reply(&self.routes).await,
+        };
+        (response.status(), response.into_body())
+    }
+}
+
+/// Signs the call of a `credits.aleo` function.
+pub fn sign_credits<R: Rng + CryptoRng>(private_key: &PrivateKey<CurrentNetwork>, function: &str, inputs: &[String], rng: &mut R) -> Request<CurrentNetwork> {
+    let program = Program::<CurrentNetwork>::credits().unwrap();
+    let function_name = Identifier::from_str(function).unwrap();
+    let inputs = inputs.iter().map(|input| Value::from_str(input).unwrap()).collect::<Vec<_>>();
+    let input_types = program.get_function(&function_name).unwrap().input_types();
+    Request::sign(private_key, *program.id(), function_name, inputs.iter(), &input_types, rng).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console_account::Address;
+    use vm_protocol::{BroadcastResponse, ErrorResponse, EstimateFeeRequest, ExecuteRequest, FeeEstimate, ServerInfo};
+    use crate::info;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_node_without_blocks() {
+        let node = StubNode::empty();
+        for path in ["/testnet3/latest/height", "/testnet3/latest/stateRoot", "/testnet3/program/credits.aleo"] {
+            assert_eq!(warp::test::request().path(path).reply(&node.routes()).await.status(), StatusCode::NOT_FOUND);
+        }
+
+        // The server reports the node unreachable until it has a block, without failing to start.
+        let backend = RestBackend::<CurrentNetwork>::new(vec![node.url.clone()]).unwrap();
+        let query = QueryProvider::new(Arc::new(backend), Duration::ZERO).unwrap();
+        let url = node.url.clone();
+        let status = tokio::task::spawn_blocking(move || info::endpoint_status(&query, &url)).await.unwrap();
+        assert_eq!((status.reachable, status.latest_height), (false, None));
+        assert!(node.broadcasts.lock().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    async fn test_transfer_with_fee_is_broadcast() {
+        let (node, transfer, fee, fee_record) = tokio::task::spawn_blocking(|| {
+            let rng = &mut rand::thread_rng();
+            let (node, private_key) = StubNode::genesis(rng);
+            let records = node.records(&private_key);
+            let recipient = Address::try_from(&PrivateKey::<CurrentNetwork>::new(rng).unwrap()).unwrap();
+            let transfer = sign_credits(&private_key, "transfer", &[records[0].to_string(), recipient.to_string(), "100u64".to_string()], rng);
+            let fee = sign_credits(&private_key, "fee", &[records[1].to_string(), "100000u64".to_string()], rng);
+            (node, transfer, fee, records[1].to_string())
+        })
+        .await
+        .unwrap();
+        let harness = Harness::start(node);
+
+        let request = ExecuteRequest::new(transfer).with_fee(fee, fee_record, 100_000);
+        let (status, body) = harness.request("POST", "/execute_and_broadcast", &request).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+
+        let response = serde_json::from_slice::<BroadcastResponse<CurrentNetwork>>(&body).unwrap();
+        assert!(response.nodes.iter().all(|node| node.accepted), "{:?}", response.nodes);
+        let broadcasts = harness.node.broadcasts.lock();
+        assert_eq!(broadcasts.len(), 1);
+        assert_eq!(broadcasts[0].id().to_string(), response.id);
+        assert!(matches!(&broadcasts[0], Transaction::Execute(_, _, Some(_))));
+        drop(broadcasts);
+
+        // The same request returns the transaction proved for it.
+        let (status, body) = harness.request("POST", "/execute_function", &request).await;
+        assert_eq!(status, StatusCode::OK);
+        let transaction = Transaction::<CurrentNetwork>::from_str(std::str::from_utf8(&body).unwrap()).unwrap();
+        assert_eq!(transaction.id().to_string(), response.id);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    async fn test_transfer_fee_estimate() {
+        let (node, transfer) = tokio::task::spawn_blocking(|| {
+            let rng = &mut rand::thread_rng();
+            let (node, private_key) = StubNode::genesis(rng);
+            let record = node.records(&private_key)[0].to_string();
+            let recipient = Address::try_from(&PrivateKey::<CurrentNetwork>::new(rng).unwrap()).unwrap();
+            (node, sign_credits(&private_key, "transfer", &[record, recipient.to_string(), "100u64".to_string()], rng))
+        })
+        .await
+        .unwrap();
+        let harness = Harness::start(node);
+
+        let (status, body) = harness.request("POST", "/estimate_fee", EstimateFeeRequest::new(transfer)).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let estimate = serde_json::from_slice::<FeeEstimate>(&body).unwrap();
+        assert!(estimate.num_constraints > 0);
+        // The wallet prefills the recommended fee, which pays for the fee transition too.
+        assert!(estimate.minimum_fee > 0);
+        assert!(estimate.recommended_fee > estimate.minimum_fee);
+    }
+
+    /// Booting the routes builds the VM of the server, which loads the `credits.aleo` keys.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    async fn test_requests_are_checked_before_proving() {
+        let ledger = tokio::task::spawn_blocking(|| Ledger::from(ConsensusStore::open(None).unwrap()).unwrap()).await.unwrap();
+        let harness = Harness::start(StubNode::start(ledger, vec![]));
+
+        // The node has no block yet, so it does not report a height.
+        let (status, body) = harness.request("GET", "/info", ()).await;
+        assert_eq!(status, StatusCode::OK);
+        let info = serde_json::from_slice::<ServerInfo>(&body).unwrap();
+        assert_eq!((info.network.as_str(), info.ready, info.query_endpoint.reachable), ("testnet3", false, false));
+
+        let (status, body) = harness.request("POST", "/execute_function", serde_json::json!({ "network": "mainnet", "version": 1 })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(serde_json::from_slice::<ErrorResponse>(&body).unwrap().code, "wrong_network");
+
+        let (status, body) = harness.request("POST", "/execute_batch", serde_json::json!([])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(serde_json::from_slice::<ErrorResponse>(&body).unwrap().code, "bad_request");
+    }
+}
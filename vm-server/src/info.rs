@@ -0,0 +1,54 @@
+use snarkvm_console_network::Network;
+use vm_protocol::EndpointStatus;
+use crate::query::QueryProvider;
+
+/// The snarkVM version the server is built against, as resolved in `Cargo.lock`.
+pub const SNARKVM_VERSION: &str = env!("SNARKVM_VERSION");
+
+/// Fetches the latest height through the query provider to report whether the nodes answer, this blocks.
+pub fn endpoint_status<N: Network>(query: &QueryProvider<N>, url: &str) -> EndpointStatus {
+    let url = url.to_string();
+    match query.latest_height() {
+        Ok(height) => EndpointStatus { url, reachable: true, latest_height: Some(height), error: None },
+        Err(error) => EndpointStatus { url, reachable: false, latest_height: None, error: Some(error.to_string()) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use snarkvm_console_network::prelude::Uniform;
+    use snarkvm_synthesizer::Program;
+    use vm_protocol::ProgramInfo;
+    use crate::query::MockBackend;
+    use crate::CurrentNetwork;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_endpoint_status() {
+        let roots = (0..43).map(|_| <CurrentNetwork as Network>::StateRoot::rand(&mut rand::thread_rng())).collect();
+        let chain = QueryProvider::new(Arc::new(MockBackend::new(roots, vec![])), Duration::ZERO).unwrap();
+        let empty = QueryProvider::<CurrentNetwork>::new(Arc::new(MockBackend::new(vec![], vec![])), Duration::ZERO).unwrap();
+
+        let (reachable, unreachable) = tokio::task::spawn_blocking(move || {
+            (endpoint_status(&chain, "http://node.example.com"), endpoint_status(&empty, "http://node.example.com"))
+        })
+        .await
+        .unwrap();
+
+        assert!(reachable.reachable);
+        assert_eq!(reachable.latest_height, Some(42));
+        assert!(!unreachable.reachable);
+        assert!(unreachable.error.is_some());
+
+        let credits = ProgramInfo::from(&Program::<CurrentNetwork>::credits().unwrap());
+        assert_eq!(credits.id, "credits.aleo");
+        assert!(credits.functions.iter().any(|function| function == "fee"));
+    }
+
+    #[test]
+    fn test_snarkvm_version() {
+        assert_eq!(SNARKVM_VERSION.split('.').filter(|part| part.parse::<u32>().is_ok()).count(), 3, "{}", SNARKVM_VERSION);
+    }
+}
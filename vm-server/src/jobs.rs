@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use snarkvm_console_network::Network;
+use snarkvm_synthesizer::{Fee, Transaction};
+use tokio::sync::{oneshot, watch, Semaphore};
+use tracing::Instrument;
+use crate::error::{ErrorResponse, RestError};
+
+/// A blocking proving task, run on the worker pool. It reports its stages to the progress.
+pub type ProvingTask<N> = Box<dyn FnOnce(&Progress) -> Result<JobOutput<N>, RestError> + Send + 'static>;
+
+/// The stage of a proving job, streamed to clients by `GET /jobs/{id}/events`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The job waits for a free worker.
+    Queued,
+    /// The execution circuits are synthesized and proved.
+    Execution,
+    /// The deployment is synthesized.
+    Deployment,
+    /// The fee circuit is executed.
+    FeeCircuit,
+    /// The inclusion assignments of the fee record are prepared from its state path.
+    FeeAssignments,
+    /// The inclusion proof of the fee is computed.
+    FeeInclusion,
+    /// The transaction is verified.
+    Verification,
+    /// The job produced a transaction or a fee.
+    Done,
+    /// The job failed.
+    Failed,
+    /// The job was cancelled.
+    Cancelled,
+}
+
+impl Stage {
+    /// Returns `true` if the job is finished.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Stage::Done | Stage::Failed | Stage::Cancelled)
+    }
+}
+
+/// Reports the stages of a running job, the default progress reports nowhere.
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<watch::Sender<Stage>>>);
+
+impl Progress {
+    /// Moves the job to the stage, unless it is already finished.
+    pub fn report(&self, stage: Stage) {
+        tracing::debug!("Proving stage: {:?}", stage);
+        if let Some(sender) = &self.0 {
+            sender.send_if_modified(|current| {
+                let changed = !current.is_final() && *current != stage;
+                if changed {
+                    *current = stage;
+                }
+                changed
+            });
+        }
+    }
+}
+
+/// The result of a proving job.
+#[derive(Clone, Debug)]
+pub enum JobOutput<N: Network> {
+    /// A transaction, possibly without a fee.
+    Transaction(Box<Transaction<N>>),
+    /// A fee proved on its own, to be assembled with an execution.
+    Fee(Box<Fee<N>>),
+    /// The circuit keys of a function, synthesized to estimate its fee.
+    Keys,
+}
+
+impl<N: Network> JobOutput<N> {
+    /// Returns the transaction, the task is known to prove one.
+    pub fn into_transaction(self) -> Result<Transaction<N>, RestError> {
+        match self {
+            JobOutput::Transaction(transaction) => Ok(*transaction),
+            _ => Err(RestError::Proving("the job did not prove a transaction".to_string())),
+        }
+    }
+
+    /// Returns the fee, the task is known to prove one.
+    pub fn into_fee(self) -> Result<Fee<N>, RestError> {
+        match self {
+            JobOutput::Fee(fee) => Ok(*fee),
+            _ => Err(RestError::Proving("the job did not prove a fee".to_string())),
+        }
+    }
+}
+
+impl<N: Network> From<Transaction<N>> for JobOutput<N> {
+    fn from(transaction: Transaction<N>) -> Self {
+        JobOutput::Transaction(Box::new(transaction))
+    }
+}
+
+impl<N: Network> From<Fee<N>> for JobOutput<N> {
+    fn from(fee: Fee<N>) -> Self {
+        JobOutput::Fee(Box::new(fee))
+    }
+}
+
+/// The state of a proving job.
+#[derive(Clone, Debug)]
+pub enum JobState<N: Network> {
+    /// The job waits for a free worker.
+    Queued,
+    /// The job is being proved.
+    Proving,
+    /// The job produced a transaction or a fee.
+    Done(JobOutput<N>),
+    /// The job failed.
+    Failed(RestError),
+    /// The job was cancelled by the client.
+    Cancelled,
+}
+
+impl<N: Network> JobState<N> {
+    /// Returns `true` if the job will not change anymore.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Proving)
+    }
+}
+
+/// The JSON view of a proving job.
+#[derive(Clone, Debug, Serialize)]
+#[serde(bound = "")]
+pub struct JobStatus<N: Network> {
+    pub id: String,
+    /// One of `queued`, `proving`, `done`, `failed` or `cancelled`.
+    pub status: &'static str,
+    /// The stage the job is at.
+    pub stage: Stage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction<N>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Fee<N>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+struct Job<N: Network> {
+    state: JobState<N>,
+    /// The name of the API key that submitted the job, only that key may see or cancel it.
+    owner: Option<String>,
+    /// Set once the client cancels the job, a running proof is discarded when it completes.
+    cancelled: bool,
+    finished_at: Option<Instant>,
+    /// The stage of the job, watched by the event streams.
+    stage: Arc<watch::Sender<Stage>>,
+    /// Notified with the result of the job, for synchronous callers.
+    waiter: Option<oneshot::Sender<Result<JobOutput<N>, RestError>>>,
+}
+
+/// The proving job queue, backed by a bounded pool of blocking workers.
+#[derive(Clone)]
+pub struct Jobs<N: Network> {
+    jobs: Arc<Mutex<HashMap<String, Job<N>>>>,
+    workers: Arc<Semaphore>,
+    max_queue: usize,
+    retention: Duration,
+    /// Set once the server shuts down, new jobs are refused.
+    draining: Arc<AtomicBool>,
+}
+
+impl<N: Network> Jobs<N> {
+    /// Initializes a queue running at most `workers` proofs at once and holding at most
+    /// `max_queue` unfinished jobs. Finished jobs are kept for `retention`.
+    pub fn new(workers: usize, max_queue: usize, retention: Duration) -> Self {
+        Self { jobs: Default::default(), workers: Arc::new(Semaphore::new(workers)), max_queue, retention, draining: Default::default() }
+    }
+
+    /// Enqueues the task for the `owner` client and returns the job ID.
+    pub fn submit(&self, task: ProvingTask<N>, owner: Option<String>) -> Result<String, RestError> {
+        self.spawn(task, owner, None)
+    }
+
+    /// Enqueues the task and waits for its result.
+    pub async fn run(&self, task: ProvingTask<N>) -> Result<JobOutput<N>, RestError> {
+        let (sender, receiver) = oneshot::channel();
+        let id = self.spawn(task, None, Some(sender))?;
+        receiver.await.unwrap_or_else(|_| Err(RestError::Cancelled(format!("job '{}' was dropped", id))))
+    }
+
+    /// Returns the number of queued and proving jobs.
+    pub fn depth(&self) -> usize {
+        self.jobs.lock().values().filter(|job| !job.state.is_finished()).count()
+    }
+
+    /// Returns the status of the job submitted by the client.
+    pub fn status(&self, id: &str, client: Option<&str>) -> Result<JobStatus<N>, RestError> {
+        let mut jobs = self.jobs.lock();
+        let job = Self::owned(&mut jobs, id, client)?;
+        Ok(Self::to_status(id, job))
+    }
+
+    /// Returns the stages of the job submitted by the client, starting with the current one.
+    pub fn subscribe(&self, id: &str, client: Option<&str>) -> Result<watch::Receiver<Stage>, RestError> {
+        let mut jobs = self.jobs.lock();
+        let job = Self::owned(&mut jobs, id, client)?;
+        Ok(job.stage.subscribe())
+    }
+
+    /// Cancels the job submitted by the client. A queued job never starts, a proving job runs to
+    /// completion but its result is discarded.
+    pub fn cancel(&self, id: &str, client: Option<&str>) -> Result<JobStatus<N>, RestError> {
+        let mut jobs = self.jobs.lock();
+        let job = Self::owned(&mut jobs, id, client)?;
+        if !job.state.is_finished() {
+            job.cancelled = true;
+        }
+        if matches!(job.state, JobState::Queued) {
+            Self::complete(id, job, Err(RestError::Cancelled(format!("job '{}' was cancelled", id))));
+        }
+        Ok(Self::to_status(id, job))
+    }
+
+    /// Refuses new jobs, the unfinished ones keep running.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` once the queue is draining.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Waits until no job is queued or proving, returns `false` if some still are after `timeout`.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.depth() > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+
+    /// Fails the unfinished jobs, so their clients get an answer before the server stops. The
+    /// running proofs are discarded when they complete.
+    pub fn abort_unfinished(&self) {
+        let mut jobs = self.jobs.lock();
+        for (id, job) in jobs.iter_mut().filter(|(_, job)| !job.state.is_finished()) {
+            job.cancelled = true;
+            Self::complete(id, job, Err(RestError::Draining(format!("the server stopped before job '{}' finished", id))));
+        }
+    }
+
+    /// Returns the job, unknown to clients other than the one that submitted it.
+    fn owned<'a>(jobs: &'a mut HashMap<String, Job<N>>, id: &str, client: Option<&str>) -> Result<&'a mut Job<N>, RestError> {
+        jobs.get_mut(id).filter(|job| job.owner.as_deref() == client).ok_or_else(|| RestError::UnknownJob(id.to_string()))
+    }
+
+    fn spawn(
+        &self,
+        task: ProvingTask<N>,
+        owner: Option<String>,
+        waiter: Option<oneshot::Sender<Result<JobOutput<N>, RestError>>>,
+    ) -> Result<String, RestError> {
+        if self.is_draining() {
+            return Err(RestError::Draining("the server takes no new jobs".to_string()));
+        }
+        let id = {
+            let mut jobs = self.jobs.lock();
+            // Forget the finished jobs past their retention.
+            jobs.retain(|_, job| job.finished_at.is_none_or(|at| at.elapsed() < self.retention));
+            let pending = jobs.values().filter(|job| !job.state.is_finished()).count();
+            if pending >= self.max_queue {
+                return Err(RestError::QueueFull(format!("{} jobs are pending", pending)));
+            }
+            let id = format!("{:032x}", rand::thread_rng().gen::<u128>());
+            let stage = Arc::new(watch::channel(Stage::Queued).0);
+            jobs.insert(id.clone(), Job { state: JobState::Queued, owner, cancelled: false, finished_at: None, stage, waiter });
+            id
+        };
+
+        // The job runs in the span of the request that submitted it, so its logs carry the request ID.
+        let span = tracing::info_span!("job", id = %id);
+        let jobs = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            // Wait for a free worker.
+            let _permit = match jobs.workers.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            let progress = match jobs.start(&job_id) {
+                Some(progress) => progress,
+                None => return,
+            };
+            let span = tracing::Span::current();
+            let result = match tokio::task::spawn_blocking(move || span.in_scope(|| task(&progress))).await {
+                Ok(result) => result,
+                Err(e) => Err(RestError::Proving(format!("the proving task panicked: {}", e))),
+            };
+            jobs.finish(&job_id, result);
+        }
+        .instrument(span));
+        Ok(id)
+    }
+
+    /// Marks the job as proving and returns its progress, or `None` if it was cancelled while queued.
+    fn start(&self, id: &str) -> Option<Progress> {
+        match self.jobs.lock().get_mut(id) {
+            Some(job) if !job.cancelled => {
+                job.state = JobState::Proving;
+                Some(Progress(Some(job.stage.clone())))
+            }
+            _ => None,
+        }
+    }
+
+    fn finish(&self, id: &str, result: Result<JobOutput<N>, RestError>) {
+        // A job aborted by the shutdown is already finished.
+        if let Some(job) = self.jobs.lock().get_mut(id).filter(|job| !job.state.is_finished()) {
+            let result = match job.cancelled {
+                true => Err(RestError::Cancelled(format!("job '{}' was cancelled", id))),
+                false => result,
+            };
+            Self::complete(id, job, result);
+        }
+    }
+
+    fn complete(id: &str, job: &mut Job<N>, result: Result<JobOutput<N>, RestError>) {
+        job.state = match &result {
+            Ok(output) => JobState::Done(output.clone()),
+            Err(RestError::Cancelled(_)) => JobState::Cancelled,
+            Err(error) => JobState::Failed(error.clone()),
+        };
+        job.stage.send_replace(match job.state {
+            JobState::Cancelled => Stage::Cancelled,
+            JobState::Failed(_) => Stage::Failed,
+            _ => Stage::Done,
+        });
+        job.finished_at = Some(Instant::now());
+        if let Some(waiter) = job.waiter.take() {
+            let _ = waiter.send(result);
+        }
+        tracing::debug!("job '{}' finished", id);
+    }
+
+    fn to_status(id: &str, job: &Job<N>) -> JobStatus<N> {
+        let (status, transaction, fee, error) = match &job.state {
+            JobState::Queued => ("queued", None, None, None),
+            JobState::Proving => ("proving", None, None, None),
+            JobState::Done(JobOutput::Transaction(transaction)) => ("done", Some(*transaction.clone()), None, None),
+            JobState::Done(JobOutput::Fee(fee)) => ("done", None, Some(*fee.clone()), None),
+            JobState::Done(JobOutput::Keys) => ("done", None, None, None),
+            JobState::Failed(error) => ("failed", None, None, Some(error.to_response())),
+            JobState::Cancelled => ("cancelled", None, None, None),
+        };
+        JobStatus { id: id.to_string(), status, stage: *job.stage.borrow(), transaction, fee, error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CurrentNetwork;
+
+    /// Returns a task that blocks until the returned sender is dropped, then fails.
+    fn blocking_task() -> (std::sync::mpsc::Sender<()>, ProvingTask<CurrentNetwork>) {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let task = Box::new(move |progress: &Progress| {
+            progress.report(Stage::Execution);
+            let _ = receiver.recv();
+            Err(RestError::Proving("released".to_string()))
+        });
+        (sender, task)
+    }
+
+    #[tokio::test]
+    async fn test_failed_job() {
+        let jobs = Jobs::<CurrentNetwork>::new(1, 4, Duration::from_secs(60));
+        let result = jobs.run(Box::new(|_| Err(RestError::Proving("boom".to_string())))).await;
+        assert!(matches!(result, Err(RestError::Proving(_))));
+        assert_eq!(jobs.depth(), 0);
+        assert!(matches!(jobs.status("missing", None), Err(RestError::UnknownJob(_))));
+    }
+
+    #[tokio::test]
+    async fn test_queue_limit_and_cancellation() {
+        let jobs = Jobs::<CurrentNetwork>::new(1, 2, Duration::from_secs(60));
+        let (release_first, first) = blocking_task();
+        let (_release_second, second) = blocking_task();
+        let first = jobs.submit(first, None).unwrap();
+        let second = jobs.submit(second, None).unwrap();
+
+        // The queue is full until a job finishes.
+        let (_, third) = blocking_task();
+        assert!(matches!(jobs.submit(third, None), Err(RestError::QueueFull(_))));
+
+        // The second job waits behind the first and never starts once cancelled.
+        while jobs.status(&first, None).unwrap().status != "proving" {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(jobs.status(&second, None).unwrap().status, "queued");
+        assert_eq!(jobs.status(&second, None).unwrap().stage, Stage::Queued);
+        assert_eq!(jobs.cancel(&second, None).unwrap().status, "cancelled");
+        assert_eq!(jobs.depth(), 1);
+
+        drop(release_first);
+        while !jobs.status(&first, None).unwrap().status.eq("failed") {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let status = jobs.status(&first, None).unwrap();
+        assert_eq!(status.error.unwrap().details.as_deref(), Some("released"));
+        assert_eq!(jobs.status(&second, None).unwrap().status, "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_private_to_their_client() {
+        // Without workers, the job stays queued.
+        let jobs = Jobs::<CurrentNetwork>::new(0, 4, Duration::from_secs(60));
+        let (_, task) = blocking_task();
+        let id = jobs.submit(task, Some("wallet".to_string())).unwrap();
+
+        // Other clients see no such job, and cannot cancel it.
+        for client in [Some("explorer"), None] {
+            assert!(matches!(jobs.status(&id, client), Err(RestError::UnknownJob(_))));
+            assert!(matches!(jobs.subscribe(&id, client), Err(RestError::UnknownJob(_))));
+            assert!(matches!(jobs.cancel(&id, client), Err(RestError::UnknownJob(_))));
+        }
+        assert_eq!(jobs.depth(), 1);
+        assert_eq!(jobs.cancel(&id, Some("wallet")).unwrap().status, "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let jobs = Jobs::<CurrentNetwork>::new(1, 4, Duration::from_secs(60));
+        assert!(jobs.wait_idle(Duration::ZERO).await);
+        let (release, task) = blocking_task();
+        let id = jobs.submit(task, None).unwrap();
+
+        // New jobs are refused, the running one keeps the queue busy until the deadline.
+        jobs.drain();
+        let (_, refused) = blocking_task();
+        assert!(matches!(jobs.submit(refused, None), Err(RestError::Draining(_))));
+        assert!(!jobs.wait_idle(Duration::from_millis(200)).await);
+
+        // Aborted jobs report the shutdown, even once their proof completes.
+        jobs.abort_unfinished();
+        assert_eq!(jobs.depth(), 0);
+        drop(release);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let status = jobs.status(&id, None).unwrap();
+        assert_eq!((status.status, status.error.unwrap().code.as_str()), ("failed", "draining"));
+    }
+
+    #[tokio::test]
+    async fn test_progress() {
+        let jobs = Jobs::<CurrentNetwork>::new(1, 4, Duration::from_secs(60));
+        let (release, task) = blocking_task();
+        let id = jobs.submit(task, None).unwrap();
+        let mut stages = jobs.subscribe(&id, None).unwrap();
+        while *stages.borrow_and_update() != Stage::Execution {
+            stages.changed().await.unwrap();
+        }
+        assert_eq!(jobs.status(&id, None).unwrap().stage, Stage::Execution);
+
+        // The final stage is not overwritten by a late report.
+        drop(release);
+        while !stages.borrow_and_update().is_final() {
+            stages.changed().await.unwrap();
+        }
+        assert_eq!(*stages.borrow(), Stage::Failed);
+        let finished = Arc::new(watch::channel(Stage::Done).0);
+        Progress(Some(finished.clone())).report(Stage::Execution);
+        assert_eq!(*finished.borrow(), Stage::Done);
+        assert!(matches!(jobs.subscribe("unknown", None), Err(RestError::UnknownJob(_))));
+    }
+}
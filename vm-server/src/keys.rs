@@ -0,0 +1,152 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use anyhow::Context;
+use circuit::Aleo;
+use snarkvm_console_network::Network;
+use snarkvm_console_program::{Identifier, ProgramID};
+use snarkvm_synthesizer::{ConsensusStorage, Stack};
+use crate::metrics::metrics;
+use crate::programs::Programs;
+
+/// The `credits.aleo` functions warmed up on every start, as they back every transfer.
+pub const DEFAULT_WARMUP: [&str; 2] = ["credits.aleo/transfer", "credits.aleo/fee"];
+
+/// A function whose keys are synthesized at startup, written `program.aleo/function`, or
+/// `program.aleo` for every function of the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WarmupTarget<N: Network> {
+    pub program_id: ProgramID<N>,
+    pub function_name: Option<Identifier<N>>,
+}
+
+impl<N: Network> FromStr for WarmupTarget<N> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (program_id, function_name) = match s.split_once('/') {
+            Some((program_id, function_name)) => (program_id, Some(function_name)),
+            None => (s, None),
+        };
+        let program_id = ProgramID::from_str(program_id).with_context(|| format!("invalid program ID in warm-up target '{}'", s))?;
+        let function_name = function_name
+            .map(Identifier::from_str)
+            .transpose()
+            .with_context(|| format!("invalid function name in warm-up target '{}'", s))?;
+        Ok(Self { program_id, function_name })
+    }
+}
+
+impl<N: Network> fmt::Display for WarmupTarget<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function_name {
+            Some(function_name) => write!(f, "{}/{}", self.program_id, function_name),
+            None => write!(f, "{}", self.program_id),
+        }
+    }
+}
+
+/// Whether the warm-up has completed and the server proves at full speed.
+#[derive(Clone, Debug, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn set_ready(&self) {
+        self.0.store(true, Ordering::Release)
+    }
+}
+
+/// Synthesizes the circuit keys of the warm-up targets ahead of the first request.
+///
+/// The first execution of a function otherwise synthesizes its keys on the request path.
+/// Synthesized keys are kept by the storage, so a disk-backed server only pays this once.
+#[derive(Clone)]
+pub struct KeyCache<N: Network, C: ConsensusStorage<N>> {
+    programs: Programs<N, C>,
+    readiness: Readiness,
+}
+
+impl<N: Network, C: ConsensusStorage<N>> KeyCache<N, C> {
+    pub fn new(programs: Programs<N, C>) -> Self {
+        Self { programs, readiness: Readiness::default() }
+    }
+
+    /// Returns the readiness flag, set once `warm_up` completes.
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    /// Synthesizes the keys of the targets and marks the server as ready.
+    ///
+    /// A target that fails is logged and skipped, so a missing program never keeps the server unready.
+    pub async fn warm_up<A: Aleo<Network = N>>(&self, targets: Vec<WarmupTarget<N>>) {
+        let mut synthesized = 0;
+        for target in targets {
+            match self.warm_up_target::<A>(&target).await {
+                Ok(count) => synthesized += count,
+                Err(e) => tracing::warn!("Failed to warm up '{}': {:#}", target, e),
+            }
+        }
+        tracing::debug!("Warm-up completed, synthesized {} keys", synthesized);
+        self.readiness.set_ready();
+    }
+
+    async fn warm_up_target<A: Aleo<Network = N>>(&self, target: &WarmupTarget<N>) -> anyhow::Result<usize> {
+        let stack = self.programs.stack(&target.program_id).await?;
+        let function_names = match &target.function_name {
+            Some(function_name) => vec![*function_name],
+            None => stack.program().functions().keys().copied().collect(),
+        };
+        let storage = self.programs.storage().clone();
+        tokio::task::spawn_blocking(move || {
+            let count = synthesize_keys::<N, A>(&stack, &function_names)?;
+            if count > 0 {
+                storage.save_keys(&stack)?;
+            }
+            Ok(count)
+        })
+        .await?
+    }
+}
+
+/// Synthesizes the missing keys of the functions, returns the number of functions synthesized.
+pub fn synthesize_keys<N: Network, A: Aleo<Network = N>>(stack: &Stack<N>, function_names: &[Identifier<N>]) -> anyhow::Result<usize> {
+    let rng = &mut rand::thread_rng();
+    let mut count = 0;
+    for function_name in function_names {
+        let cached = stack.contains_proving_key(function_name) && stack.contains_verifying_key(function_name);
+        metrics().observe_key_lookup(cached);
+        if !cached {
+            stack.synthesize_key::<A, _>(function_name, rng)?;
+            tracing::debug!("Synthesized the keys of '{}/{}'", stack.program_id(), function_name);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CurrentNetwork;
+
+    #[test]
+    fn test_warmup_targets() {
+        let target = WarmupTarget::<CurrentNetwork>::from_str("credits.aleo/transfer").unwrap();
+        assert_eq!(target.program_id.to_string(), "credits.aleo");
+        assert_eq!(target.function_name.unwrap().to_string(), "transfer");
+        assert_eq!(target.to_string(), "credits.aleo/transfer");
+
+        let target = WarmupTarget::<CurrentNetwork>::from_str("token.aleo").unwrap();
+        assert!(target.function_name.is_none());
+
+        for invalid in ["token", "token.aleo/", "token.aleo/mint/burn"] {
+            assert!(WarmupTarget::<CurrentNetwork>::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
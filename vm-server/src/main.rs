@@ -6,148 +6,740 @@
 // This file is part of aleo-wallet-test.
 //
 
-use std::net::SocketAddr;
+mod auth;
+mod broadcast;
+mod config;
+mod error;
+mod fees;
+#[cfg(test)]
+mod harness;
+mod info;
+mod jobs;
+mod keys;
+mod metrics;
+mod programs;
+mod query;
+mod replays;
+mod shutdown;
+mod storage;
+mod tls;
+mod verify;
+
+use std::convert::Infallible;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use anyhow::ensure;
+use clap::Parser;
 use circuit::{Aleo, AleoV0, Environment};
 use parking_lot::RwLock;
-use snarkvm_console_account::PrivateKey;
 use snarkvm_console_network::prelude::{CryptoRng, Rng};
 use snarkvm_console_network::Network;
 use snarkvm_console_program::{Identifier, Plaintext, ProgramID, Record, Request, Response};
-use snarkvm_synthesizer::{Authorization, CallMetrics, CallStack, cast_ref, ConsensusMemory, ConsensusStorage, ConsensusStore, Execution, Fee, Inclusion, InclusionAssignment, Query, Stack, Transaction, Transition, VM};
-use tracing::debug;
+use snarkvm_synthesizer::{Authorization, CallMetrics, CallStack, cast_ref, ConsensusMemory, ConsensusStorage, ConsensusStore, Execution, Fee, Inclusion, InclusionAssignment, Program, Query, Stack, Transaction, Transition, VM};
+use tracing::{debug, info, Instrument};
+use tracing_subscriber::EnvFilter;
 use warp::{Filter, reject, Rejection, Reply};
-use warp::http::HeaderName;
+use warp::http::StatusCode;
+use warp::sse::Event;
 use anyhow::anyhow;
 use rand::prelude::ThreadRng;
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(bound = "")]
-pub struct MyRequest<N: Network> {
-    request: Request<N>,
-    fee_request: Option<Request<N>>,
-    fee_record: Option<String>,
-    fee: Option<u64>,
-}
-
-const TRANSFER_CONF_DATA: &[u8] = include_bytes!("../../wasm-lib/src/transfer_conf");
+use vm_protocol::{
+    AssembleRequest, BatchItem, BroadcastResponse, DeployRequest, EstimateFeeRequest, ExecuteRequest, ProgramInfo, ProveExecutionRequest,
+    ProveFeeRequest, ProvedExecution, ProvedFee, ServerInfo, VerifyTransactionRequest, PROTOCOL_VERSION,
+};
+use crate::auth::Auth;
+use crate::broadcast::Broadcaster;
+use crate::config::{Cli, Config};
+use crate::error::{handle_rejection, OrReject, RestError};
+use crate::info::SNARKVM_VERSION;
+use crate::jobs::{JobOutput, Jobs, Progress, ProvingTask, Stage};
+use crate::keys::{KeyCache, Readiness};
+use crate::metrics::metrics;
+use crate::programs::Programs;
+use crate::query::{QueryProvider, RestBackend};
+use crate::replays::{Claim, Replays};
+use crate::storage::Storage;
 
 type CurrentNetwork = <AleoV0 as Environment>::Network;
 
+/// The header carrying the ID of a request, generated if the client does not set it.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
 #[tokio::main]
 async fn main() {
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_header(HeaderName::from_static("content-type"))
-        .allow_methods(vec!["GET", "POST", "OPTIONS"]);
+    // Log to stderr, filtered by `RUST_LOG`.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("vm_server=info")))
+        .with_writer(std::io::stderr)
+        .init();
+
+    // Load and validate the configuration.
+    let config = match Config::load::<CurrentNetwork>(Cli::parse()) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("vm-server: invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let cors = config.cors();
+
+    // Read the TLS certificate first, so a bad one fails the start before the keys are synthesized.
+    let certificates = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert_path), Some(key_path)) => match tls::Certificates::load(cert_path, key_path) {
+            Ok(certificates) => Some(certificates),
+            Err(e) => {
+                eprintln!("vm-server: failed to load the TLS certificate: {:#}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
 
     let store = ConsensusStore::<CurrentNetwork, ConsensusMemory<CurrentNetwork>>::open(None).unwrap();
     let vm = VM::from(store).unwrap();
 
+    // Restore the programs and keys kept by the storage.
+    let storage = match Storage::open(config.storage, config.storage_path.clone()) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("vm-server: failed to open the storage: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let restored = match storage.restore(&mut vm.process().write()) {
+        Ok(restored) => restored,
+        Err(e) => {
+            eprintln!("vm-server: failed to restore the storage: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    debug!("Restored {} programs from storage", restored.len());
+    // Query the chain state through the configured nodes, failing over in order.
+    let backend = RestBackend::<CurrentNetwork>::new(config.query_endpoints());
+    let query = match backend.and_then(|backend| QueryProvider::new(Arc::new(backend), Duration::from_millis(config.state_root_ttl_ms))) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("vm-server: failed to start the query provider: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let programs = Programs::new(vm, query, storage, restored);
+    let jobs = Jobs::new(config.workers, config.max_queue, Duration::from_secs(config.job_retention_secs));
+    let replays = Replays::new(Duration::from_secs(config.replay_window_secs));
+
+    // Synthesize the keys of the warm-up targets in the background, `/ready` reports when it is done.
+    let key_cache = KeyCache::new(programs.clone());
+    let readiness = key_cache.readiness();
+    let targets = config.warmup_targets::<CurrentNetwork>().expect("the configuration was validated");
+    tokio::spawn(async move { key_cache.warm_up::<AleoV0>(targets).await });
+
     // Initialize the routes.
-    let routes = routes::<CurrentNetwork, AleoV0, ConsensusMemory<CurrentNetwork>>(vm);
+    let routes = routes::<CurrentNetwork, AleoV0, ConsensusMemory<CurrentNetwork>>(programs, config.clone(), jobs.clone(), replays, readiness, Auth::new(config.api_keys.clone()));
 
     // Add custom logging for each request.
-    let custom_log = warp::log::custom(|info| match info.remote_addr() {
-        Some(addr) => debug!("Received '{} {}' from '{addr}' ({})", info.method(), info.path(), info.status()),
-        None => debug!("Received '{} {}' ({})", info.method(), info.path(), info.status()),
+    let custom_log = warp::log::custom(|info| {
+        metrics().observe_request(info.path(), info.status().as_u16());
+        match info.remote_addr() {
+            Some(addr) => debug!("Received '{} {}' from '{addr}' ({})", info.method(), info.path(), info.status()),
+            None => debug!("Received '{} {}' ({})", info.method(), info.path(), info.status()),
+        }
     });
 
-    // Start the server.
-    let addr = SocketAddr::from_str("0.0.0.0:17777").unwrap();
-    warp::serve(routes.with(cors).with(custom_log)).run(addr).await
+    // Start the server, each request is handled in a span carrying its ID. On SIGTERM or SIGINT,
+    // the jobs are drained before the server stops accepting connections.
+    let drained = shutdown::drain_on_signal(jobs, Duration::from_secs(config.drain_timeout_secs));
+    let routes = routes.recover(handle_rejection).with(cors).with(custom_log).with(warp::trace(request_span));
+    match certificates {
+        Some(certificates) => {
+            if config.tls_reload_secs > 0 {
+                certificates.watch(Duration::from_secs(config.tls_reload_secs));
+            }
+            if let Err(e) = tls::serve(warp::service(routes), config.bind, certificates, drained).await {
+                eprintln!("vm-server: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        None => {
+            let server = match warp::serve(routes).try_bind_with_graceful_shutdown(config.bind, drained) {
+                Ok((_, server)) => server,
+                Err(e) => {
+                    eprintln!("vm-server: failed to listen on '{}': {}", config.bind, e);
+                    std::process::exit(1);
+                }
+            };
+            info!("Listening on http://{}", config.bind);
+            server.await;
+        }
+    }
+    info!("Stopped");
+    // Proofs aborted by the drain timeout would otherwise hold the runtime open until they complete.
+    std::process::exit(0);
+}
+
+/// Returns the span of a request, identified by its `x-request-id` header or a random ID.
+fn request_span(info: warp::trace::Info) -> tracing::Span {
+    let id = match info.request_headers().get(REQUEST_ID_HEADER).and_then(|id| id.to_str().ok()) {
+        Some(id) => id.to_string(),
+        None => format!("{:016x}", rand::random::<u64>()),
+    };
+    tracing::info_span!("request", id = %id, method = %info.method(), path = %info.path())
 }
 
-fn routes<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(vm: VM<N, C>) -> impl Filter<Extract=(impl Reply, ), Error=Rejection> + Clone {
+fn routes<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>, readiness: Readiness, auth: Auth) -> impl Filter<Extract=(impl Reply, ), Error=Rejection> + Clone {
+    let (body_limit, max_batch) = (config.body_limit, config.max_batch);
+    let with_programs = warp::any().map(move || programs.clone());
+    let with_config = warp::any().map(move || config.clone());
+    let with_jobs = warp::any().map(move || jobs.clone());
+    let with_replays = warp::any().map(move || replays.clone());
+
+    // GET /metrics
+    let get_metrics = warp::get()
+        .and(warp::path!("metrics"))
+        .and(with_jobs.clone())
+        .and_then(get_metrics::<N>);
+
     // POST /execute_function
-    warp::post()
+    let execute_function = warp::post()
         .and(warp::path!("execute_function"))
-        .and(warp::body::content_length_limit(16 * 1024 * 1024))
-        .and(warp::body::json())
-        .and(warp::any().map(move || vm.clone()))
-        .and_then(execute_function::<N, A, C>)
-}
-
-async fn execute_function<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(request: MyRequest<N>, vm: VM<N, C>) -> anyhow::Result<impl Reply, Rejection> {
-    let stack = vm.process().read().get_stack(request.request.program_id()).or_reject()?.clone();
-    let authorization = Authorization::new(&[request.request.clone()]);
-    let private_key = PrivateKey::<N>::from_str("APrivateKey1zkp5EYonCQEWFuTA3mDDgdun3dQhp4pMXZs9wuSZAKzcHAr").unwrap();
-    // Construct the call stack.
-    let call_stack = CallStack::Authorize(vec![request.request], private_key, authorization.clone());
-    // Initialize an RNG.
-    let rng = &mut rand::thread_rng();
-    // Construct the authorization from the function.
-    let _response = stack.execute_function::<A, _>(call_stack, rng).or_reject()?;
-
-    let mut fee = None;
-    // Prepare the fees.
-    if request.fee_request.is_some() {
-        fee = match request.fee_record {
-            Some(record) => {
-                let record = Record::<N, Plaintext<N>>::from_str(&record).map_err(|e| anyhow!(e)).or_reject()?;
-                let fee_amount = request.fee.unwrap_or(0);
-
-                Some((record, fee_amount))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(execute_function::<N, A, C>);
+
+    // POST /execute_batch
+    let execute_batch = warp::post()
+        .and(warp::path!("execute_batch"))
+        .and(auth::json_batch::<N, _>(auth.clone(), body_limit, max_batch))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(execute_batch::<N, A, C>);
+
+    // POST /execute_and_broadcast
+    let execute_and_broadcast = warp::post()
+        .and(warp::path!("execute_and_broadcast"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(execute_and_broadcast::<N, A, C>);
+
+    // POST /deploy
+    let deploy = warp::post()
+        .and(warp::path!("deploy"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(deploy::<N, A, C>);
+
+    // POST /prove_execution
+    let prove_execution = warp::post()
+        .and(warp::path!("prove_execution"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(prove_execution::<N, A, C>);
+
+    // POST /prove_fee
+    let prove_fee = warp::post()
+        .and(warp::path!("prove_fee"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(prove_fee::<N, A, C>);
+
+    // POST /assemble_transaction
+    let assemble_transaction = warp::post()
+        .and(warp::path!("assemble_transaction"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, false))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and_then(assemble_transaction::<N, A, C>);
+
+    // POST /estimate_fee
+    let estimate_fee = warp::post()
+        .and(warp::path!("estimate_fee"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and_then(estimate_fee::<N, A, C>);
+
+    // POST /verify_transaction
+    let verify_transaction = warp::post()
+        .and(warp::path!("verify_transaction"))
+        .and(auth::json_body::<N, _>(auth.clone(), body_limit, false))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and_then(verify_transaction::<N, A, C>);
+
+    // POST /jobs
+    let submit_job = warp::post()
+        .and(warp::path!("jobs"))
+        .and(auth::client_json_body::<N, _>(auth.clone(), body_limit, true))
+        .and(with_programs.clone())
+        .and(with_config.clone())
+        .and(with_jobs.clone())
+        .and(with_replays.clone())
+        .and_then(submit_job::<N, A, C>);
+
+    // GET /jobs/{id}
+    let get_job = warp::get()
+        .and(warp::path!("jobs" / String))
+        .and(auth::check(auth.clone()))
+        .and(with_jobs.clone())
+        .and_then(get_job::<N>);
+
+    // GET /jobs/{id}/events
+    let job_events = warp::get()
+        .and(warp::path!("jobs" / String / "events"))
+        .and(auth::check(auth.clone()))
+        .and(with_jobs.clone())
+        .and_then(job_events::<N>);
+
+    // DELETE /jobs/{id}
+    let cancel_job = warp::delete()
+        .and(warp::path!("jobs" / String))
+        .and(auth::check(auth))
+        .and(with_jobs.clone())
+        .and_then(cancel_job::<N>);
+
+    // GET /health
+    let health = warp::get().and(warp::path!("health")).and(with_jobs).map(|jobs: Jobs<N>| match jobs.is_draining() {
+        true => warp::reply::with_status(warp::reply::json(&serde_json::json!({ "status": "draining" })), StatusCode::SERVICE_UNAVAILABLE),
+        false => warp::reply::with_status(warp::reply::json(&serde_json::json!({ "status": "ok" })), StatusCode::OK),
+    });
+
+    // GET /info
+    let with_readiness = warp::any().map(move || readiness.clone());
+    let info = warp::get()
+        .and(warp::path!("info"))
+        .and(with_programs)
+        .and(with_config)
+        .and(with_readiness.clone())
+        .and_then(info::<N, C>);
+
+    // GET /ready
+    let ready = warp::get()
+        .and(warp::path!("ready"))
+        .and(with_readiness)
+        .and_then(ready);
+
+    execute_function
+        .or(execute_batch)
+        .or(execute_and_broadcast)
+        .or(deploy)
+        .or(prove_execution)
+        .or(prove_fee)
+        .or(assemble_transaction)
+        .or(estimate_fee).or(verify_transaction).or(submit_job).or(get_job).or(job_events).or(cancel_job).or(health).or(info).or(ready).or(get_metrics)
+}
+
+/// Proves the request on the job queue and waits for the transaction.
+async fn execute_function<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(request: ExecuteRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let task = prepare_execution::<N, A, C>(request, &programs, &config, &replays).await?;
+    let transaction = jobs.run(task).await.and_then(JobOutput::into_transaction).map_err(reject::custom)?;
+    info!("Proved transaction '{}'", transaction.id());
+    Ok(transaction.to_string())
+}
+
+/// Proves the requests concurrently on the job queue and returns the result of each request,
+/// in order. A failed or malformed request does not fail the others.
+async fn execute_batch<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(requests: Vec<Result<ExecuteRequest<N>, RestError>>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let tasks = requests
+        .into_iter()
+        .map(|request| {
+            let (programs, config, jobs, replays) = (programs.clone(), config.clone(), jobs.clone(), replays.clone());
+            tokio::spawn(
+                async move {
+                    let request = request?;
+                    let task = prepare_execution::<N, A, C>(request, &programs, &config, &replays).await.map_err(RestError::from_rejection)?;
+                    jobs.run(task).await.and_then(JobOutput::into_transaction)
+                }
+                .in_current_span(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let result = task.await.unwrap_or_else(|e| Err(RestError::Proving(format!("the request panicked: {}", e))));
+        results.push(match result {
+            Ok(transaction) => BatchItem { transaction: Some(transaction), error: None },
+            Err(error) => BatchItem { transaction: None, error: Some(error.to_response()) },
+        });
+    }
+    debug!("Proved {} of {} batched requests", results.iter().filter(|item| item.transaction.is_some()).count(), results.len());
+    Ok(warp::reply::json(&results))
+}
+
+/// Proves the request, then relays the transaction to the configured nodes.
+async fn execute_and_broadcast<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(request: ExecuteRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let broadcaster = Broadcaster::new(config.broadcast_nodes.clone(), config.broadcast_retries, Duration::from_millis(config.broadcast_retry_delay_ms));
+    if broadcaster.is_empty() {
+        return Err(reject::custom(RestError::BadRequest("no broadcast nodes are configured".to_string())));
+    }
+    let task = prepare_execution::<N, A, C>(request, &programs, &config, &replays).await?;
+    let transaction = jobs.run(task).await.and_then(JobOutput::into_transaction).map_err(reject::custom)?;
+
+    let nodes = broadcaster.broadcast(&transaction).await.map_err(reject::custom)?;
+    if !nodes.iter().any(|node| node.accepted) {
+        let errors = nodes.iter().map(|node| format!("{}: {}", node.node, node.error.as_deref().unwrap_or_default())).collect::<Vec<_>>();
+        return Err(reject::custom(RestError::Broadcast(errors.join("; "))));
+    }
+    debug!("Broadcast '{}' to {} nodes", transaction.id(), nodes.iter().filter(|node| node.accepted).count());
+    Ok(warp::reply::json(&BroadcastResponse { id: transaction.id().to_string(), transaction, nodes }))
+}
+
+/// Synthesizes the deployment of the program and proves its fee, then returns the transaction.
+///
+/// The fee record is claimed as for executions, so it cannot pay for two deployments.
+async fn deploy<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(request: DeployRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let program = Program::<N>::from_str(&request.program).or_reject(RestError::BadRequest)?;
+    // Load the imports, and ensure the program is not deployed yet.
+    for import in program.imports().keys() {
+        programs.stack(import).await.map_err(reject::custom)?;
+    }
+    match programs.stack(program.id()).await {
+        Ok(_) => return Err(reject::custom(RestError::BadRequest(format!("'{}' is already deployed", program.id())))),
+        Err(RestError::UnknownProgram(_)) => (),
+        Err(error) => return Err(reject::custom(error)),
+    }
+
+    fees::check_fee_kind(request.fee_kind).map_err(reject::custom)?;
+    let minimum = fees::minimum_fee(fees::estimate_deployment_size(&program), config.fee_per_byte);
+    let (fee_request, amount) = prepare_fee::<N, A, C>(request.fee_request, &request.fee_record, request.fee, minimum, &programs).await?;
+    let claim = replays.claim_fee(&fee_request).map_err(reject::custom)?;
+
+    let vm = programs.vm().clone();
+    let (query, state_root_window) = (programs.query().clone(), config.state_root_window);
+    let task: ProvingTask<N> = Box::new(move |progress: &Progress| {
+        let rng = &mut rand::thread_rng();
+        let start = Instant::now();
+        progress.report(Stage::Deployment);
+        let deployment = vm.deploy(&program, rng).map_err(RestError::from_proving)?;
+        metrics().observe_proving("deployment", start);
+        debug!("Proving a deployment fee of {} gates", amount);
+        let (_, fee, _) = execute_fee::<N, A, ThreadRng, C>(&vm, &fee_request, Some(query.query()), progress, rng).map_err(RestError::from_proving)?;
+        let transaction = Transaction::from_deployment(deployment, fee).map_err(RestError::from_proving)?;
+        progress.report(Stage::Verification);
+        verify::verify_transaction::<N, A, C>(&vm, &transaction, &query, state_root_window)?;
+        claim.keep();
+        Ok(transaction.into())
+    });
+    let transaction = jobs.run(task).await.and_then(JobOutput::into_transaction).map_err(reject::custom)?;
+    Ok(transaction.to_string())
+}
+
+/// Proves the request without a fee, the execution is assembled with a fee by `/assemble_transaction`.
+async fn prove_execution<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(body: ProveExecutionRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let request = ExecuteRequest::new(body.request).with_calls(body.calls);
+    let task = prepare_execution::<N, A, C>(request, &programs, &config, &replays).await?;
+    let transaction = jobs.run(task).await.and_then(JobOutput::into_transaction).map_err(reject::custom)?;
+    let (id, execution) = match transaction {
+        Transaction::Execute(id, execution, _) => (id, execution),
+        Transaction::Deploy(..) => return Err(reject::custom(RestError::Proving("the execution proved a deployment".to_string()))),
+    };
+    let minimum_fee = fees::minimum_fee(fees::execution_size(&execution), config.fee_per_byte);
+    Ok(warp::reply::json(&ProvedExecution { execution_id: id.to_string(), execution, minimum_fee }))
+}
+
+/// Proves a fee on its own, for an execution proved by `/prove_execution`.
+///
+/// Fees do not commit to the execution they pay for, so the minimum fee is only enforced when
+/// the fee is assembled with the execution. The fee record stays claimed for the replay window.
+async fn prove_fee<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(body: ProveFeeRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    fees::check_fee_kind(body.fee_kind).map_err(reject::custom)?;
+    let (fee_request, amount) = prepare_fee::<N, A, C>(body.fee_request, &body.fee_record, body.fee, 0, &programs).await?;
+    let claim = replays.claim_fee(&fee_request).map_err(reject::custom)?;
+
+    let vm = programs.vm().clone();
+    let (query, state_root_window) = (programs.query().clone(), config.state_root_window);
+    let task: ProvingTask<N> = Box::new(move |progress: &Progress| {
+        debug!("Proving a fee of {} gates", amount);
+        let (_, fee, _) = execute_fee::<N, A, ThreadRng, C>(&vm, &fee_request, Some(query.query()), progress, &mut rand::thread_rng()).map_err(RestError::from_proving)?;
+        progress.report(Stage::Verification);
+        verify::verify_fee(&vm, &fee, &query, state_root_window)?;
+        claim.keep();
+        Ok(fee.into())
+    });
+    let fee = jobs.run(task).await.and_then(JobOutput::into_fee).map_err(reject::custom)?;
+    Ok(warp::reply::json(&ProvedFee { execution_id: body.execution_id.to_string(), fee, amount }))
+}
+
+/// Assembles an execution and a fee proved separately into a transaction, and verifies it.
+async fn assemble_transaction<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(body: AssembleRequest<N>, programs: Programs<N, C>, config: Arc<Config>) -> anyhow::Result<impl Reply, Rejection> {
+    if let Some(fee) = &body.fee {
+        fees::check_fee_covers(&body.execution, fee, config.fee_per_byte).map_err(reject::custom)?;
+    }
+    for transition in body.execution.transitions() {
+        programs.stack(transition.program_id()).await.map_err(reject::custom)?;
+    }
+    let transaction = Transaction::from_execution(body.execution, body.fee).or_reject(RestError::BadRequest)?;
+
+    let (vm, query) = (programs.vm().clone(), programs.query().clone());
+    let transaction = tokio::task::spawn_blocking(move || {
+        verify::verify_transaction::<N, A, C>(&vm, &transaction, &query, config.state_root_window).map(|_| transaction)
+    })
+    .await
+    .map_err(|e| reject::custom(RestError::Verification(format!("the verification panicked: {}", e))))?
+    .map_err(reject::custom)?;
+    Ok(transaction.to_string())
+}
+
+/// Estimates the size, constraints and fee of the execution of the request, without proving it.
+async fn estimate_fee<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(body: EstimateFeeRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let request = body.request;
+    let requests = std::iter::once(request.clone()).chain(body.calls).collect::<Vec<_>>();
+    let stack = load_stacks(&requests, &programs).await?;
+    // Replay the signed requests for the outputs.
+    let response = replay_authorization::<N, A>(&stack, &Authorization::new(&requests)).or_reject(RestError::Authorization)?;
+
+    // The constraints of the function are those of its circuit keys. Missing keys cost about as
+    // much as a proof to synthesize, so they are synthesized on the job queue.
+    let function_name = *request.function_name();
+    if !(stack.contains_proving_key(&function_name) && stack.contains_verifying_key(&function_name)) {
+        let (stack, storage) = (stack.clone(), programs.storage().clone());
+        let task: ProvingTask<N> = Box::new(move |_: &Progress| {
+            keys::synthesize_keys::<N, A>(&stack, &[function_name]).map_err(RestError::from_proving)?;
+            if let Err(e) = storage.save_keys(&stack) {
+                tracing::warn!("Failed to store the keys of '{}': {:#}", stack.program_id(), e);
             }
-            None => None,
-        };
+            Ok(JobOutput::Keys)
+        });
+        jobs.run(task).await.map_err(reject::custom)?;
+    }
+    let num_instructions = stack.get_function(&function_name).or_reject(RestError::Proving)?.instructions().len();
+    let num_constraints = stack.get_verifying_key(&function_name).or_reject(RestError::Proving)?.circuit_info.num_constraints;
+
+    let execution_size = fees::estimate_execution_size(&requests, &response);
+    Ok(warp::reply::json(&fees::fee_estimate(execution_size, num_instructions, num_constraints, config.fee_per_byte)))
+}
+
+/// Verifies a transaction, including its global state roots against the query endpoint.
+async fn verify_transaction<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(body: VerifyTransactionRequest<N>, programs: Programs<N, C>, config: Arc<Config>) -> anyhow::Result<impl Reply, Rejection> {
+    let transaction = body.transaction;
+    // Load the programs the transaction executes, or the imports of the program it deploys.
+    let program_ids = match &transaction {
+        Transaction::Deploy(_, deployment, _) => deployment.program().imports().keys().copied().collect::<Vec<_>>(),
+        Transaction::Execute(_, execution, _) => execution.transitions().map(|transition| *transition.program_id()).collect(),
+    };
+    for program_id in program_ids {
+        programs.stack(&program_id).await.map_err(reject::custom)?;
+    }
+
+    let (vm, query) = (programs.vm().clone(), programs.query().clone());
+    let id = transaction.id();
+    tokio::task::spawn_blocking(move || verify::verify_transaction::<N, A, C>(&vm, &transaction, &query, config.state_root_window))
+        .await
+        .map_err(|e| reject::custom(RestError::Verification(format!("the verification panicked: {}", e))))?
+        .map_err(reject::custom)?;
+    Ok(warp::reply::json(&serde_json::json!({ "id": id.to_string(), "valid": true })))
+}
+
+/// Enqueues the request on the job queue and returns the job ID.
+async fn submit_job<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(client: Option<String>, request: ExecuteRequest<N>, programs: Programs<N, C>, config: Arc<Config>, jobs: Jobs<N>, replays: Replays<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let task = prepare_execution::<N, A, C>(request, &programs, &config, &replays).await?;
+    let id = jobs.submit(task, client.clone()).map_err(reject::custom)?;
+    debug!("Queued job '{}' ({} pending)", id, jobs.depth());
+    let status = jobs.status(&id, client.as_deref()).map_err(reject::custom)?;
+    Ok(warp::reply::with_status(warp::reply::json(&status), StatusCode::ACCEPTED))
+}
+
+/// Returns the status of a job, only to the client that submitted it.
+async fn get_job<N: Network>(id: String, client: Option<String>, jobs: Jobs<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let status = jobs.status(&id, client.as_deref()).map_err(reject::custom)?;
+    Ok(warp::reply::json(&status))
+}
+
+/// Streams the stages of the job as server-sent `stage` events, and ends with a `done`, `failed`
+/// or `cancelled` event carrying its status.
+async fn job_events<N: Network>(id: String, client: Option<String>, jobs: Jobs<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let stages = jobs.subscribe(&id, client.as_deref()).map_err(reject::custom)?;
+    let events = futures_util::stream::unfold((Some(stages), true), move |(stages, first)| {
+        let (jobs, id, client) = (jobs.clone(), id.clone(), client.clone());
+        async move {
+            let mut stages = stages?;
+            // The job expired if its stages are closed.
+            if !first && stages.changed().await.is_err() {
+                return None;
+            }
+            let stage = *stages.borrow_and_update();
+            if stage.is_final() {
+                let status = jobs.status(&id, client.as_deref()).ok()?;
+                let event = Event::default().event(status.status).json_data(&status).ok()?;
+                return Some((Ok::<_, Infallible>(event), (None, false)));
+            }
+            let event = Event::default().event("stage").json_data(stage).ok()?;
+            Some((Ok(event), (Some(stages), false)))
+        }
+    });
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+/// Cancels a job, only for the client that submitted it.
+async fn cancel_job<N: Network>(id: String, client: Option<String>, jobs: Jobs<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let status = jobs.cancel(&id, client.as_deref()).map_err(reject::custom)?;
+    Ok(warp::reply::json(&status))
+}
+
+/// Returns the network, versions and loaded programs of the server, and whether its query endpoint answers.
+async fn info<N: Network, C: ConsensusStorage<N>>(programs: Programs<N, C>, config: Arc<Config>, readiness: Readiness) -> anyhow::Result<impl Reply, Rejection> {
+    let loaded = programs.loaded().iter().map(ProgramInfo::from).collect();
+    let query = programs.query().clone();
+    let url = config.query_endpoint.clone();
+    let query_endpoint = tokio::task::spawn_blocking(move || info::endpoint_status(&query, &url))
+        .await
+        .map_err(|e| reject::custom(RestError::Query(format!("the query endpoint check panicked: {}", e))))?;
+    Ok(warp::reply::json(&ServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        snarkvm_version: SNARKVM_VERSION.to_string(),
+        network_id: N::ID,
+        network: config::network_name::<N>().or_reject(RestError::Query)?.to_string(),
+        ready: readiness.is_ready(),
+        programs: loaded,
+        query_endpoint,
+    }))
+}
+
+/// Returns 200 once the warm-up completed, and 503 until then.
+async fn ready(readiness: Readiness) -> anyhow::Result<impl Reply, Rejection> {
+    match readiness.is_ready() {
+        true => Ok(warp::reply::json(&serde_json::json!({ "ready": true }))),
+        false => Err(reject::custom(RestError::NotReady("the circuit keys are being synthesized".to_string()))),
+    }
+}
+
+/// Returns the metrics in the Prometheus text format.
+async fn get_metrics<N: Network>(jobs: Jobs<N>) -> anyhow::Result<impl Reply, Rejection> {
+    let metrics = metrics();
+    metrics.queue_depth.set(jobs.depth() as i64);
+    Ok(warp::reply::with_header(metrics.render(), "content-type", "text/plain; version=0.0.4"))
+}
+
+/// Returns the stack of the function of the first request, loading the programs of all requests.
+async fn load_stacks<N: Network, C: ConsensusStorage<N>>(requests: &[Request<N>], programs: &Programs<N, C>) -> Result<Stack<N>, Rejection> {
+    let (request, calls) = requests.split_first().ok_or_else(|| reject::custom(RestError::BadRequest("no request to execute".to_string())))?;
+    for call in calls {
+        programs.stack(call.program_id()).await.map_err(reject::custom)?;
     }
+    programs.stack(request.program_id()).await.map_err(reject::custom)
+}
 
-    let file_contents = std::str::from_utf8(TRANSFER_CONF_DATA).unwrap();
-    let conf = file_contents
-        .split('\n')
-        .map(|c| c.to_string())
-        .collect::<Vec<String>>();
+/// Checks the request and returns the task proving it, loading the programs if needed. The
+/// requests of the calls the function makes are authorized with it.
+///
+/// A request proved with the same fee within the replay window returns its transaction, and a
+/// request spending a record another request spends, or proved with another fee, is refused.
+async fn prepare_execution<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(request: ExecuteRequest<N>, programs: &Programs<N, C>, config: &Config, replays: &Replays<N>) -> Result<ProvingTask<N>, Rejection> {
+    let requests = request.requests();
+    let stack = load_stacks(&requests, programs).await?;
+    let vm = programs.vm().clone();
+    let storage = programs.storage().clone();
+    let authorization = Authorization::new(&requests);
+    // Replay the signed requests to check them before proving.
+    let response = replay_authorization::<N, A>(&stack, &authorization).or_reject(RestError::Authorization)?;
+
+    // Check the fee pays the declared amount from the fee record, and covers the execution.
+    fees::check_fee_kind(request.fee_kind).map_err(reject::custom)?;
+    let fee = match (request.fee_request, request.fee_record) {
+        (Some(fee_request), Some(fee_record)) => {
+            let minimum = fees::minimum_fee(fees::estimate_execution_size(&requests, &response), config.fee_per_byte);
+            Some(prepare_fee::<N, A, C>(fee_request, &fee_record, request.fee, minimum, programs).await?)
+        }
+        (None, None) if request.fee.is_none() => None,
+        _ => return Err(reject::custom(RestError::BadRequest("'fee_request', 'fee_record' and 'fee' must be set together".to_string()))),
+    };
 
-    println!("{}", conf[1]);
-    let query = Some(Query::<N, C::BlockStorage>::from(conf[1].clone()));
+    let claim = match replays.claim(&requests, fee.as_ref().map(|(fee_request, _)| fee_request)).map_err(reject::custom)? {
+        Claim::New(claim) => claim,
+        Claim::Proved(transaction) => {
+            debug!("Returning transaction '{}' proved for the same request", transaction.id());
+            return Ok(Box::new(move |_: &Progress| Ok(JobOutput::Transaction(transaction))));
+        }
+    };
+    let (query, state_root_window) = (programs.query().clone(), config.state_root_window);
+
+    Ok(Box::new(move |progress: &Progress| {
+        // Initialize an RNG.
+        let rng = &mut rand::thread_rng();
+        let transaction = execute_authorization_with_additional_fee::<N, A, C, ThreadRng>(&vm, authorization, fee, Some(query.query()), progress, rng)
+            .map_err(RestError::from_proving)?;
+        // Verify the transaction before returning it, so a malformed one never reaches a node.
+        progress.report(Stage::Verification);
+        verify::verify_transaction::<N, A, C>(&vm, &transaction, &query, state_root_window)?;
+        // Keep the keys synthesized for the execution.
+        if let Err(e) = storage.save_keys(&stack) {
+            tracing::warn!("Failed to store the keys of '{}': {:#}", stack.program_id(), e);
+        }
+        claim.finish(&transaction);
+        Ok(transaction.into())
+    }))
+}
 
-    let result = execute_authorization_with_additional_fee::<N, A, C, ThreadRng>(&vm, &private_key, &request.fee_request, authorization, fee, query, rng, &stack).or_reject()?;
-    println!("execute_function ok");
-    Ok(result.to_string())
+/// Checks the signed fee request pays the declared amount from the fee record, and covers the
+/// `minimum`. Returns the fee request and its amount.
+async fn prepare_fee<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>>(fee_request: Request<N>, fee_record: &str, declared: Option<u64>, minimum: u64, programs: &Programs<N, C>) -> Result<(Request<N>, u64), Rejection> {
+    let fee_record = Record::<N, Plaintext<N>>::from_str(fee_record).map_err(|e| anyhow!(e)).or_reject(RestError::InvalidRecord)?;
+    let amount = fees::check_fee(&fee_request, &fee_record, declared, minimum).map_err(reject::custom)?;
+    let credits = programs.stack(fee_request.program_id()).await.map_err(reject::custom)?;
+    replay_authorization::<N, A>(&credits, &Authorization::new(std::slice::from_ref(&fee_request))).or_reject(RestError::Authorization)?;
+    Ok((fee_request, amount))
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Proves the execution and the fee, the fee request and its amount are checked by the caller.
 fn execute_authorization_with_additional_fee<N: Network, A: Aleo<Network=N>, C: ConsensusStorage<N>, R: Rng + CryptoRng>(
     vm: &VM<N, C>,
-    private_key: &PrivateKey<N>,
-    fee_request: &Option<Request<N>>,
     authorization: Authorization<N>,
-    additional_fee: Option<(Record<N, Plaintext<N>>, u64)>,
+    additional_fee: Option<(Request<N>, u64)>,
     query: Option<Query<N, C::BlockStorage>>,
+    progress: &Progress,
     rng: &mut R,
-    stack: &Stack<N>,
 ) -> anyhow::Result<Transaction<N>> {
     // Compute the execution.
-    let (_response, execution, _metrics) = vm.execute(authorization, query.clone(), rng)?;
+    progress.report(Stage::Execution);
+    let request = authorization.peek_next()?;
+    let cached = vm.process().read().get_stack(request.program_id())?.contains_proving_key(request.function_name());
+    metrics().observe_key_lookup(cached);
+    let start = Instant::now();
+    let (_response, execution, call_metrics) = vm.execute(authorization, query.clone(), rng)?;
+    metrics().observe_proving("execution", start);
+    metrics().observe_calls(&call_metrics);
 
-    let mut additional_fee_f = None;
     // Compute the additional fee, if it is present.
-    if let Some(fee_request) = fee_request {
-        additional_fee_f = match additional_fee {
-            Some((_credits, _additional_fee_in_gates)) => {
-                Some(execute_fee::<N, A, R, C>(vm, private_key, fee_request, query, rng, stack)?.1)
-            }
-            None => None,
-        };
-    }
+    let additional_fee = match additional_fee {
+        Some((fee_request, amount)) => {
+            debug!("Proving a fee of {} gates", amount);
+            Some(execute_fee::<N, A, R, C>(vm, &fee_request, query, progress, rng)?.1)
+        }
+        None => None,
+    };
 
-    Transaction::from_execution(execution, additional_fee_f)
+    Transaction::from_execution(execution, additional_fee)
 }
 
 #[allow(clippy::type_complexity)]
 fn execute_fee<N: Network, A: Aleo<Network=N>, R: Rng + CryptoRng, C: ConsensusStorage<N>>(
     vm: &VM<N, C>,
-    private_key: &PrivateKey<N>,
     fee_request: &Request<N>,
     query: Option<Query<N, C::BlockStorage>>,
+    progress: &Progress,
     rng: &mut R,
-    stack: &Stack<N>,
 ) -> anyhow::Result<(Response<N>, Fee<N>, Vec<CallMetrics<N>>)> {
-    println!("VM::execute_fee");
+    let _span = tracing::debug_span!("execute_fee").entered();
+    let start = Instant::now();
 
     // Prepare the query.
     let query = match query {
@@ -155,20 +747,12 @@ fn execute_fee<N: Network, A: Aleo<Network=N>, R: Rng + CryptoRng, C: ConsensusS
         None => Query::VM(vm.block_store().clone()),
     };
 
-    println!("Process::execute_fee");
-
     // Ensure the fee has the correct program ID.
     let program_id = ProgramID::<N>::from_str("credits.aleo")?;
     // Ensure the fee has the correct function.
     let function_name = Identifier::<N>::from_str("fee")?;
     // Initialize the authorization.
-    let authorization = Authorization::new(&[fee_request.clone()]);
-    println!("Initialize the authorization");
-    // Construct the call stack.
-    let call_stack = CallStack::Authorize(vec![fee_request.clone()], *private_key, authorization.clone());
-    // Construct the authorization from the function.
-    let _response = stack.execute_function::<A, R>(call_stack, rng)?;
-    println!("Construct the authorization from the function");
+    let authorization = Authorization::new(std::slice::from_ref(fee_request));
 
     // Retrieve the main request (without popping it).
     let request = authorization.peek_next()?;
@@ -180,13 +764,12 @@ fn execute_fee<N: Network, A: Aleo<Network=N>, R: Rng + CryptoRng, C: ConsensusS
     let metrics = Arc::new(RwLock::new(Vec::new()));
     // Initialize the call stack.
     let call_stack = CallStack::execute(authorization, execution.clone(), inclusion.clone(), metrics.clone())?;
-    // Prepare the stack.
-    let binding = vm.process();
+    // Prepare the stack, without holding the process lock while proving.
+    let stack = vm.process().read().get_stack(request.program_id())?.clone();
     // Execute the circuit.
-    let binding = binding.read();
-    let stack = binding.get_stack(request.program_id())?;
+    progress.report(Stage::FeeCircuit);
     let response = stack.execute_function::<A, R>(call_stack, rng)?;
-    println!("Execute the circuit");
+    debug!("Executed the fee circuit");
 
     // Extract the execution.
     let execution = Arc::try_unwrap(execution).unwrap().into_inner();
@@ -200,47 +783,127 @@ fn execute_fee<N: Network, A: Aleo<Network=N>, R: Rng + CryptoRng, C: ConsensusS
     let (response, fee_transition, inclusion, metrics) = (response, execution.peek()?.clone(), inclusion, metrics);
 
     // Prepare the assignments.
+    progress.report(Stage::FeeAssignments);
     let assignments = {
         let fee_transition = cast_ref!(fee_transition as Transition<N>);
         let inclusion = cast_ref!(inclusion as Inclusion<N>);
         inclusion.prepare_fee(fee_transition, query)?
     };
     let assignments = cast_ref!(assignments as Vec<InclusionAssignment<N>>);
-    println!("Prepare the assignments");
+    debug!("Prepared the inclusion assignments");
 
     // Compute the inclusion proof and construct the fee.
+    progress.report(Stage::FeeInclusion);
     let fee = inclusion.prove_fee::<A, R>(fee_transition, assignments, rng)?;
-    println!("Compute the inclusion proof and construct the fee");
+    debug!("Computed the inclusion proof");
 
     // Prepare the return.
     let response = cast_ref!(response as Response<N>).clone();
     let fee = cast_ref!(fee as Fee<N>).clone();
     let metrics = cast_ref!(metrics as Vec<CallMetrics<N>>).clone();
-    println!("Prepare the response, fee, and metrics");
-
-    println!("execute_fee finished");
+    crate::metrics::metrics().observe_proving("fee", start);
+    crate::metrics::metrics().observe_calls(&metrics);
 
     // Return the response, fee, metrics.
     Ok((response, fee, metrics))
 }
 
-/// A trait to unwrap a `Result` or `Reject`.
-pub trait OrReject<T> {
-    /// Returns the result if it is successful, otherwise returns a rejection.
-    fn or_reject(self) -> Result<T, Rejection>;
+/// Replays the signed requests of the authorization against the stack, without proving.
+///
+/// This verifies the client's signatures and computes the response, so the server never needs
+/// a private key of its own. The given authorization is left untouched.
+fn replay_authorization<N: Network, A: Aleo<Network=N>>(stack: &Stack<N>, authorization: &Authorization<N>) -> anyhow::Result<Response<N>> {
+    stack.evaluate_function::<A>(CallStack::evaluate(authorization.replicate())?)
 }
 
-impl<T> OrReject<T> for anyhow::Result<T> {
-    /// Returns the result if it is successful, otherwise returns a rejection.
-    fn or_reject(self) -> Result<T, Rejection> {
-        self.map_err(|e| reject::custom(RestError::Request(e.to_string())))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console_account::{PrivateKey, ViewKey};
+    use snarkvm_console_program::Value;
+    use snarkvm_synthesizer::Block;
+
+    #[test]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    fn test_transaction_is_independent_of_server_keys() {
+        let rng = &mut rand::thread_rng();
+
+        // Fund a client account with a local genesis block, so the state queries stay in-process.
+        let private_key = PrivateKey::<CurrentNetwork>::new(rng).unwrap();
+        let view_key = ViewKey::try_from(&private_key).unwrap();
+        let store = ConsensusStore::<CurrentNetwork, ConsensusMemory<CurrentNetwork>>::open(None).unwrap();
+        let vm = VM::from(store).unwrap();
+        let genesis = Block::genesis(&vm, &private_key, rng).unwrap();
+        vm.add_next_block(&genesis).unwrap();
+        let (_, record) = genesis.records().next().unwrap();
+        let record = record.decrypt(&view_key).unwrap();
+
+        // Sign the transfer on the client side.
+        let program = Program::<CurrentNetwork>::credits().unwrap();
+        let function_name = Identifier::from_str("transfer").unwrap();
+        let recipient = PrivateKey::<CurrentNetwork>::new(rng).unwrap();
+        let inputs = [
+            Value::Record(record),
+            Value::from_str(&snarkvm_console_account::Address::try_from(&recipient).unwrap().to_string()).unwrap(),
+            Value::from_str("100u64").unwrap(),
+        ];
+        let input_types = program.get_function(&function_name).unwrap().input_types();
+        let request = Request::sign(&private_key, *program.id(), function_name, inputs.iter(), &input_types, rng).unwrap();
+
+        // Prove the same request twice, the server holds no key material of its own.
+        let stack = vm.process().read().get_stack(program.id()).unwrap().clone();
+        let prove = |rng: &mut ThreadRng| {
+            let authorization = Authorization::new(std::slice::from_ref(&request));
+            replay_authorization::<CurrentNetwork, AleoV0>(&stack, &authorization).unwrap();
+            execute_authorization_with_additional_fee::<CurrentNetwork, AleoV0, _, _>(&vm, authorization, None, None, &Progress::default(), rng).unwrap()
+        };
+        let first = prove(rng);
+        let second = prove(rng);
+
+        // The transitions are fully determined by the client's signed request.
+        assert!(vm.verify_transaction(&first));
+        assert!(vm.verify_transaction(&second));
+        let first = first.transitions().collect::<Vec<_>>();
+        let second = second.transitions().collect::<Vec<_>>();
+        assert_eq!(first.len(), 1);
+        assert_eq!(*first[0].tpk(), request.to_tpk());
+        assert_eq!(first[0].id(), second[0].id());
     }
-}
 
-/// An enum of error handlers for the REST API server.
-#[derive(Debug)]
-pub enum RestError {
-    Request(String),
+    #[test]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    fn test_execute_function_calling_an_import() {
+        let rng = &mut rand::thread_rng();
+        let private_key = PrivateKey::<CurrentNetwork>::new(rng).unwrap();
+        let store = ConsensusStore::<CurrentNetwork, ConsensusMemory<CurrentNetwork>>::open(None).unwrap();
+        let vm = VM::from(store).unwrap();
+        let genesis = Block::genesis(&vm, &private_key, rng).unwrap();
+        vm.add_next_block(&genesis).unwrap();
+        let child = Program::<CurrentNetwork>::from_str("program child.aleo;\n\nfunction double:\n    input r0 as u64.private;\n    add r0 r0 into r1;\n    output r1 as u64.private;\n").unwrap();
+        let parent = Program::<CurrentNetwork>::from_str(
+            "import child.aleo;\n\nprogram parent.aleo;\n\nfunction quadruple:\n    input r0 as u64.private;\n    call child.aleo/double r0 into r1;\n    call child.aleo/double r1 into r2;\n    output r2 as u64.private;\n",
+        )
+        .unwrap();
+        for program in [&child, &parent] {
+            vm.process().write().add_program(program).unwrap();
+        }
+
+        // The client signs the function and each call it makes.
+        let authorization = vm.process().read().authorize::<AleoV0, _>(&private_key, "parent.aleo", "quadruple", ["3u64"].into_iter(), rng).unwrap();
+        let mut signed = authorization.to_vec_deque().into_iter();
+        let request = ExecuteRequest::<CurrentNetwork>::new(signed.next().unwrap()).with_calls(signed.collect());
+        let request = serde_json::from_str::<ExecuteRequest<CurrentNetwork>>(&serde_json::to_string(&request).unwrap()).unwrap();
+        assert_eq!(request.calls.len(), 2);
+
+        // The calls cannot be authorized from the request of the function alone.
+        let stack = vm.process().read().get_stack(parent.id()).unwrap().clone();
+        assert!(replay_authorization::<CurrentNetwork, AleoV0>(&stack, &Authorization::new(std::slice::from_ref(&request.request))).is_err());
+
+        let authorization = Authorization::new(&request.requests());
+        let response = replay_authorization::<CurrentNetwork, AleoV0>(&stack, &authorization).unwrap();
+        assert_eq!(response.outputs()[0].to_string(), "12u64");
+        let transaction = execute_authorization_with_additional_fee::<CurrentNetwork, AleoV0, _, _>(&vm, authorization, None, None, &Progress::default(), rng).unwrap();
+        assert!(vm.verify_transaction(&transaction));
+        assert_eq!(transaction.transitions().count(), 3);
+    }
 }
-
-impl warp::reject::Reject for RestError {}
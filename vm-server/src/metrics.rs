@@ -0,0 +1,166 @@
+use std::sync::OnceLock;
+use std::time::Instant;
+use prometheus::{exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use snarkvm_console_network::Network;
+use snarkvm_synthesizer::CallMetrics;
+
+/// The routes counted by their path, requests to any other path are counted as `other`.
+const ROUTES: &[&str] = &[
+    "/execute_function",
+    "/execute_batch",
+    "/execute_and_broadcast",
+    "/deploy",
+    "/prove_execution",
+    "/prove_fee",
+    "/assemble_transaction",
+    "/estimate_fee",
+    "/verify_transaction",
+    "/jobs",
+    "/health",
+    "/info",
+    "/ready",
+    "/metrics",
+];
+
+/// The program whose functions are labelled by name, calls to other programs are counted as `other`.
+const LABELLED_PROGRAM: &str = "credits.aleo";
+
+/// The metrics of the server, exposed on `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// The handled requests, by route and status code.
+    pub requests: IntCounterVec,
+    /// The time spent proving, by `execution`, `fee` or `deployment`.
+    pub proving_seconds: HistogramVec,
+    /// The number of queued and proving jobs.
+    pub queue_depth: IntGauge,
+    /// The number of constraints of the proved functions.
+    pub constraints: HistogramVec,
+    /// The lookups of circuit keys, by `hit` or `miss`.
+    pub key_cache: IntCounterVec,
+    /// The lookups of the latest state root, by `hit` or `miss` of its cache.
+    pub state_root_cache: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("vm_server".to_string()), None)?;
+        let requests = IntCounterVec::new(Opts::new("requests_total", "The handled requests."), &["route", "status"])?;
+        let proving_seconds = HistogramVec::new(
+            HistogramOpts::new("proving_duration_seconds", "The time spent proving.")
+                .buckets(vec![0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 40.0, 80.0, 160.0]),
+            &["kind"],
+        )?;
+        let queue_depth = IntGauge::new("queue_depth", "The number of queued and proving jobs.")?;
+        let constraints = HistogramVec::new(
+            HistogramOpts::new("function_constraints", "The number of constraints of the proved functions.").buckets(exponential_buckets(1_000.0, 4.0, 10)?),
+            &["program", "function"],
+        )?;
+        let key_cache = IntCounterVec::new(Opts::new("key_cache_total", "The lookups of circuit keys."), &["result"])?;
+        let state_root_cache = IntCounterVec::new(Opts::new("state_root_cache_total", "The lookups of the latest state root."), &["result"])?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(proving_seconds.clone()))?;
+        registry.register(Box::new(queue_depth.clone()))?;
+        registry.register(Box::new(constraints.clone()))?;
+        registry.register(Box::new(key_cache.clone()))?;
+        registry.register(Box::new(state_root_cache.clone()))?;
+        Ok(Self { registry, requests, proving_seconds, queue_depth, constraints, key_cache, state_root_cache })
+    }
+
+    /// Counts a handled request by its route. Job IDs and unknown paths are left out of the route,
+    /// so clients cannot grow the number of series.
+    pub fn observe_request(&self, path: &str, status: u16) {
+        let route = match path.split('/').collect::<Vec<_>>().as_slice() {
+            ["", "jobs", id] if !id.is_empty() => "/jobs/{id}",
+            ["", "jobs", id, "events"] if !id.is_empty() => "/jobs/{id}/events",
+            _ => ROUTES.iter().find(|route| **route == path).copied().unwrap_or("other"),
+        };
+        self.requests.with_label_values(&[route, &status.to_string()]).inc();
+    }
+
+    /// Records the time spent proving since `start`.
+    pub fn observe_proving(&self, kind: &str, start: Instant) {
+        self.proving_seconds.with_label_values(&[kind]).observe(start.elapsed().as_secs_f64());
+    }
+
+    /// Records the constraint counts of the calls. Programs are chosen by clients, so only the
+    /// functions of `credits.aleo` keep their labels, like the routes.
+    pub fn observe_calls<N: Network>(&self, calls: &[CallMetrics<N>]) {
+        for call in calls {
+            let constraints = call.num_request_constraints + call.num_function_constraints + call.num_response_constraints;
+            let (program, function) = match call.program_id.to_string() == LABELLED_PROGRAM {
+                true => (LABELLED_PROGRAM.to_string(), call.function_name.to_string()),
+                false => ("other".to_string(), "other".to_string()),
+            };
+            self.constraints.with_label_values(&[&program, &function]).observe(constraints as f64);
+        }
+    }
+
+    /// Counts a lookup of circuit keys.
+    pub fn observe_key_lookup(&self, hit: bool) {
+        self.key_cache.with_label_values(&[if hit { "hit" } else { "miss" }]).inc();
+    }
+
+    /// Counts a lookup of the latest state root.
+    pub fn observe_state_root_lookup(&self, hit: bool) {
+        self.state_root_cache.with_label_values(&[if hit { "hit" } else { "miss" }]).inc();
+    }
+
+    /// Returns the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        match TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            Ok(()) => String::from_utf8(buffer).unwrap_or_default(),
+            Err(e) => format!("# failed to encode the metrics: {}\n", e),
+        }
+    }
+}
+
+/// Returns the metrics of the server.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("the metrics are valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use snarkvm_console_program::{Identifier, ProgramID};
+    use crate::CurrentNetwork;
+
+    #[test]
+    fn test_render_metrics() {
+        // The proving tests record into the global metrics, so the counts are checked on fresh ones.
+        let metrics = Metrics::new().unwrap();
+        metrics.observe_request("/jobs/0123abcd", 200);
+        metrics.observe_request("/jobs/0123abcd/events", 200);
+        metrics.observe_request("/random-3f2a", 404);
+        metrics.observe_request("/jobs/0123abcd/random", 404);
+        metrics.observe_request("/execute_function", 422);
+        metrics.observe_key_lookup(true);
+        metrics.observe_proving("fee", Instant::now());
+        let call = |program: &str, function: &str| CallMetrics::<CurrentNetwork> {
+            program_id: ProgramID::from_str(program).unwrap(),
+            function_name: Identifier::from_str(function).unwrap(),
+            num_instructions: 1,
+            num_request_constraints: 1_000,
+            num_function_constraints: 2_000,
+            num_response_constraints: 1_000,
+        };
+        metrics.observe_calls(&[call("credits.aleo", "transfer"), call("random_token.aleo", "mint")]);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"vm_server_requests_total{route="/jobs/{id}",status="200"} 1"#), "{}", rendered);
+        assert!(rendered.contains(r#"vm_server_requests_total{route="/jobs/{id}/events",status="200"} 1"#));
+        assert!(rendered.contains(r#"vm_server_requests_total{route="/execute_function",status="422"} 1"#));
+        assert!(rendered.contains(r#"vm_server_requests_total{route="other",status="404"} 2"#));
+        assert!(!rendered.contains("random"));
+        assert!(rendered.contains(r#"vm_server_key_cache_total{result="hit"} 1"#));
+        assert!(rendered.contains(r#"vm_server_proving_duration_seconds_count{kind="fee"} 1"#));
+        // Only the functions of credits.aleo are labelled by name.
+        assert!(rendered.contains(r#"vm_server_function_constraints_count{function="transfer",program="credits.aleo"} 1"#));
+        assert!(rendered.contains(r#"vm_server_function_constraints_count{function="other",program="other"} 1"#));
+    }
+}
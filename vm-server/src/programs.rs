@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use snarkvm_console_network::Network;
+use snarkvm_console_program::ProgramID;
+use snarkvm_synthesizer::{ConsensusStorage, Program, Stack, VM};
+use crate::error::RestError;
+use crate::query::QueryProvider;
+use crate::storage::Storage;
+
+/// The maximum number of programs fetched to load a single program and its imports.
+const MAX_PROGRAMS_PER_LOAD: usize = 32;
+
+/// The programs available for execution, loaded on demand from the query endpoint.
+///
+/// Proofs hold the process lock for their whole run, so the async routes only read the stacks
+/// kept here, and the process is only locked on blocking threads.
+#[derive(Clone)]
+pub struct Programs<N: Network, C: ConsensusStorage<N>> {
+    vm: VM<N, C>,
+    query: QueryProvider<N>,
+    storage: Storage<N>,
+    /// The IDs of the loaded programs, in load order.
+    program_ids: Arc<RwLock<Vec<ProgramID<N>>>>,
+    /// The stacks of the loaded programs, sharing their circuit keys with those of the process.
+    stacks: Arc<RwLock<HashMap<ProgramID<N>, Stack<N>>>>,
+    /// Serializes the loads, so concurrent requests fetch a program once.
+    loading: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl<N: Network, C: ConsensusStorage<N>> Programs<N, C> {
+    /// Initializes the programs of the VM, holding `credits.aleo` and the `restored` programs.
+    pub fn new(vm: VM<N, C>, query: QueryProvider<N>, storage: Storage<N>, restored: Vec<ProgramID<N>>) -> Self {
+        let program_ids = ProgramID::from_str("credits.aleo").into_iter().chain(restored).collect::<Vec<_>>();
+        let stacks = {
+            let process = vm.process();
+            let process = process.read();
+            program_ids.iter().filter_map(|id| process.get_stack(id).ok().map(|stack| (*id, stack.clone()))).collect()
+        };
+        Self {
+            vm,
+            query,
+            storage,
+            program_ids: Arc::new(RwLock::new(program_ids)),
+            stacks: Arc::new(RwLock::new(stacks)),
+            loading: Default::default(),
+        }
+    }
+
+    /// Returns the VM the programs are loaded in.
+    pub fn vm(&self) -> &VM<N, C> {
+        &self.vm
+    }
+
+    /// Returns the chain state the programs are fetched from.
+    pub fn query(&self) -> &QueryProvider<N> {
+        &self.query
+    }
+
+    /// Returns the storage of the loaded programs and keys.
+    pub fn storage(&self) -> &Storage<N> {
+        &self.storage
+    }
+
+    /// Returns the loaded programs, in load order.
+    pub fn loaded(&self) -> Vec<Program<N>> {
+        let stacks = self.stacks.read();
+        self.program_ids.read().iter().filter_map(|id| stacks.get(id).map(|stack| stack.program().clone())).collect()
+    }
+
+    /// Returns the stack of the program, fetching the program and its imports if they are not loaded.
+    pub async fn stack(&self, program_id: &ProgramID<N>) -> Result<Stack<N>, RestError> {
+        if let Some(stack) = self.stacks.read().get(program_id) {
+            return Ok(stack.clone());
+        }
+
+        let _guard = self.loading.lock().await;
+        let programs = self.clone();
+        let id = *program_id;
+        tokio::task::spawn_blocking(move || programs.load(&id))
+            .await
+            .map_err(|e| RestError::Query(format!("failed to load '{}': {}", program_id, e)))?
+    }
+
+    /// Fetches the program and its missing imports, adds them to the process, and returns the
+    /// stack of the program. Waits for the running proofs to release the process.
+    fn load(&self, program_id: &ProgramID<N>) -> Result<Stack<N>, RestError> {
+        if let Some(stack) = self.stacks.read().get(program_id) {
+            return Ok(stack.clone());
+        }
+        let programs = fetch_programs(&self.query, program_id, |id| self.stacks.read().contains_key(id))?;
+        let process = self.vm.process();
+        let mut process = process.write();
+        for program in programs {
+            if !process.contains_program(program.id()) {
+                process.add_program(&program).map_err(|e| RestError::UnknownProgram(format!("failed to load '{}': {}", program.id(), e)))?;
+                tracing::debug!("Loaded program '{}'", program.id());
+                if let Err(e) = self.storage.save_program(&program) {
+                    tracing::warn!("Failed to store program '{}': {:#}", program.id(), e);
+                }
+            }
+            let stack = process.get_stack(program.id()).map_err(|e| RestError::UnknownProgram(e.to_string()))?.clone();
+            if self.stacks.write().insert(*program.id(), stack).is_none() {
+                self.program_ids.write().push(*program.id());
+            }
+        }
+        let stack = process.get_stack(program_id).map_err(|e| RestError::UnknownProgram(e.to_string()))?;
+        Ok(stack.clone())
+    }
+}
+
+/// Fetches the program and the imports it needs that are not `loaded`, imports first.
+pub fn fetch_programs<N: Network>(
+    query: &QueryProvider<N>,
+    program_id: &ProgramID<N>,
+    loaded: impl Fn(&ProgramID<N>) -> bool,
+) -> Result<Vec<Program<N>>, RestError> {
+    let mut programs = Vec::new();
+    let mut visited = HashSet::new();
+    fetch_recursive(query, program_id, &loaded, &mut visited, &mut programs)?;
+    Ok(programs)
+}
+
+fn fetch_recursive<N: Network>(
+    query: &QueryProvider<N>,
+    program_id: &ProgramID<N>,
+    loaded: &impl Fn(&ProgramID<N>) -> bool,
+    visited: &mut HashSet<ProgramID<N>>,
+    programs: &mut Vec<Program<N>>,
+) -> Result<(), RestError> {
+    if loaded(program_id) || !visited.insert(*program_id) {
+        return Ok(());
+    }
+    if visited.len() > MAX_PROGRAMS_PER_LOAD {
+        return Err(RestError::UnknownProgram(format!("'{}' imports more than {} programs", program_id, MAX_PROGRAMS_PER_LOAD)));
+    }
+
+    let program = query.program(program_id)?;
+    if program.id() != program_id {
+        return Err(RestError::Query(format!("the query endpoint returned '{}' for '{}'", program.id(), program_id)));
+    }
+    for import in program.imports().keys() {
+        fetch_recursive(query, import, loaded, visited, programs)?;
+    }
+    programs.push(program);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::query::MockBackend;
+    use crate::CurrentNetwork;
+
+    const TOKEN: &str = r"
+program token.aleo;
+
+function mint:
+    input r0 as u64.private;
+    add r0 r0 into r1;
+    output r1 as u64.private;
+";
+
+    const EXCHANGE: &str = r"
+import token.aleo;
+
+program exchange.aleo;
+
+function swap:
+    input r0 as u64.private;
+    output r0 as u64.private;
+";
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fetch_programs_with_imports() {
+        let programs = [TOKEN, EXCHANGE].iter().map(|program| Program::<CurrentNetwork>::from_str(program).unwrap()).collect();
+        let query = QueryProvider::new(Arc::new(MockBackend::new(vec![], programs)), Duration::ZERO).unwrap();
+        let fetched = tokio::task::spawn_blocking(move || {
+            let exchange = ProgramID::from_str("exchange.aleo").unwrap();
+            let all = fetch_programs(&query, &exchange, |_| false)?;
+            let token = ProgramID::from_str("token.aleo").unwrap();
+            let missing = fetch_programs(&query, &exchange, |id| *id == token)?;
+            let unknown = fetch_programs(&query, &ProgramID::from_str("nope.aleo").unwrap(), |_| false);
+            Ok::<_, RestError>((all, missing, unknown))
+        })
+        .await
+        .unwrap();
+
+        let (all, missing, unknown) = fetched.unwrap();
+        let ids = all.iter().map(|program| program.id().to_string()).collect::<Vec<_>>();
+        assert_eq!(ids, ["token.aleo", "exchange.aleo"]);
+        assert_eq!(missing.len(), 1);
+        assert!(matches!(unknown, Err(RestError::UnknownProgram(_))));
+    }
+}
@@ -0,0 +1,309 @@
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use snarkvm_console_network::Network;
+use snarkvm_console_program::{Field, ProgramID, StatePath};
+use snarkvm_synthesizer::{BlockStorage, Program, Query};
+use warp::{Filter, Rejection, Reply};
+use crate::config::network_name;
+use crate::error::RestError;
+use crate::metrics::metrics;
+
+/// How long a request to an upstream node may take.
+pub const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A source of the chain state, these calls block.
+pub trait QueryBackend<N: Network>: Send + Sync {
+    /// Returns the latest global state root.
+    fn latest_state_root(&self) -> Result<N::StateRoot, RestError>;
+    /// Returns the latest block height.
+    fn latest_height(&self) -> Result<u32, RestError>;
+    /// Returns the global state root before the block at the given height.
+    fn previous_state_root(&self, height: u32) -> Result<N::StateRoot, RestError>;
+    /// Returns the state path of a record commitment.
+    fn state_path(&self, commitment: &Field<N>) -> Result<StatePath<N>, RestError>;
+    /// Returns a deployed program, or `UnknownProgram` if it is not deployed.
+    fn program(&self, program_id: &ProgramID<N>) -> Result<Program<N>, RestError>;
+}
+
+/// Queries the REST API of nodes, failing over to the next node when one cannot be reached.
+pub struct RestBackend<N: Network> {
+    endpoints: Vec<String>,
+    network: &'static str,
+    /// The index of the last node that answered, tried first.
+    preferred: AtomicUsize,
+    _network: PhantomData<N>,
+}
+
+impl<N: Network> RestBackend<N> {
+    /// Initializes a backend querying the nodes at the given base URLs, in order.
+    pub fn new(endpoints: Vec<String>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!endpoints.is_empty(), "at least one query endpoint is required");
+        Ok(Self { endpoints, network: network_name::<N>()?, preferred: AtomicUsize::new(0), _network: PhantomData })
+    }
+
+    /// Fetches the route from the first node that answers, or `None` if it answers 404.
+    fn get<T: DeserializeOwned>(&self, route: &str) -> Result<Option<T>, RestError> {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let mut errors = Vec::new();
+        for index in (0..self.endpoints.len()).map(|offset| (preferred + offset) % self.endpoints.len()) {
+            let url = format!("{}/{}/{}", self.endpoints[index], self.network, route);
+            let response = match ureq::get(&url).timeout(UPSTREAM_TIMEOUT).call() {
+                Ok(response) => response,
+                Err(ureq::Error::Status(404, _)) => return Ok(None),
+                Err(e) => {
+                    tracing::warn!("Failed to fetch '{}': {}", url, e);
+                    errors.push(format!("failed to fetch '{}': {}", url, e));
+                    continue;
+                }
+            };
+            self.preferred.store(index, Ordering::Relaxed);
+            return response.into_json().map(Some).map_err(|e| RestError::Query(format!("invalid response from '{}': {}", url, e)));
+        }
+        Err(RestError::Query(errors.join("; ")))
+    }
+
+    /// Fetches a route every node serves.
+    fn get_required<T: DeserializeOwned>(&self, route: &str) -> Result<T, RestError> {
+        self.get(route)?.ok_or_else(|| RestError::Query(format!("the query endpoints do not serve '{}'", route)))
+    }
+}
+
+impl<N: Network> QueryBackend<N> for RestBackend<N> {
+    fn latest_state_root(&self) -> Result<N::StateRoot, RestError> {
+        self.get_required("latest/stateRoot")
+    }
+
+    fn latest_height(&self) -> Result<u32, RestError> {
+        self.get_required("latest/height")
+    }
+
+    fn previous_state_root(&self, height: u32) -> Result<N::StateRoot, RestError> {
+        // The state root after each block is stored in the header of the next one.
+        let block = self.get_required::<serde_json::Value>(&format!("block/{}", height))?;
+        serde_json::from_value(block["header"]["previous_state_root"].clone())
+            .map_err(|e| RestError::Query(format!("block {} has no valid state root: {}", height, e)))
+    }
+
+    fn state_path(&self, commitment: &Field<N>) -> Result<StatePath<N>, RestError> {
+        self.get_required(&format!("statePath/{}", commitment))
+    }
+
+    fn program(&self, program_id: &ProgramID<N>) -> Result<Program<N>, RestError> {
+        self.get(&format!("program/{}", program_id))?.ok_or_else(|| RestError::UnknownProgram(format!("'{}' is not deployed", program_id)))
+    }
+}
+
+/// The chain state the server proves and verifies against, with the latest state root cached.
+#[derive(Clone)]
+pub struct QueryProvider<N: Network> {
+    backend: Arc<dyn QueryBackend<N>>,
+    state_root_ttl: Duration,
+    latest_state_root: Arc<Mutex<Option<(Instant, N::StateRoot)>>>,
+    /// The base URL of the relay serving the provider to snarkVM.
+    relay: String,
+}
+
+impl<N: Network> QueryProvider<N> {
+    /// Initializes the provider and starts its relay, this must be called within the Tokio runtime.
+    ///
+    /// snarkVM only queries a local ledger or a node URL, so the provider is served on a loopback
+    /// port with the routes of a node, and snarkVM is given its URL.
+    pub fn new(backend: Arc<dyn QueryBackend<N>>, state_root_ttl: Duration) -> anyhow::Result<Self> {
+        let mut provider = Self { backend, state_root_ttl, latest_state_root: Default::default(), relay: String::new() };
+        let (addr, server) = warp::serve(relay_routes(provider.clone(), network_name::<N>()?)).try_bind_ephemeral(([127, 0, 0, 1], 0))?;
+        tokio::spawn(server);
+        provider.relay = format!("http://{}", addr);
+        Ok(provider)
+    }
+
+    /// Returns the query snarkVM proves with.
+    pub fn query<B: BlockStorage<N>>(&self) -> Query<N, B> {
+        Query::from(&self.relay)
+    }
+
+    /// Returns the latest global state root, cached for the configured TTL.
+    pub fn latest_state_root(&self) -> Result<N::StateRoot, RestError> {
+        if let Some((fetched, state_root)) = *self.latest_state_root.lock() {
+            if fetched.elapsed() < self.state_root_ttl {
+                metrics().observe_state_root_lookup(true);
+                return Ok(state_root);
+            }
+        }
+        metrics().observe_state_root_lookup(false);
+        self.refresh_state_root()
+    }
+
+    /// Fetches the latest global state root, bypassing the cache.
+    pub fn refresh_state_root(&self) -> Result<N::StateRoot, RestError> {
+        let state_root = self.backend.latest_state_root()?;
+        *self.latest_state_root.lock() = Some((Instant::now(), state_root));
+        Ok(state_root)
+    }
+
+    pub fn latest_height(&self) -> Result<u32, RestError> {
+        self.backend.latest_height()
+    }
+
+    pub fn previous_state_root(&self, height: u32) -> Result<N::StateRoot, RestError> {
+        self.backend.previous_state_root(height)
+    }
+
+    pub fn state_path(&self, commitment: &Field<N>) -> Result<StatePath<N>, RestError> {
+        self.backend.state_path(commitment)
+    }
+
+    pub fn program(&self, program_id: &ProgramID<N>) -> Result<Program<N>, RestError> {
+        self.backend.program(program_id)
+    }
+}
+
+/// Serves the routes of a node snarkVM queries while proving.
+fn relay_routes<N: Network>(provider: QueryProvider<N>, network: &'static str) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let with_provider = warp::any().map(move || provider.clone());
+
+    let latest_state_root = warp::path!("latest" / "stateRoot")
+        .and(with_provider.clone())
+        .and_then(|provider: QueryProvider<N>| relay(move || provider.latest_state_root()));
+    let state_path = warp::path!("statePath" / String).and(with_provider.clone()).and_then(|commitment: String, provider: QueryProvider<N>| {
+        relay(move || {
+            let commitment = Field::<N>::from_str(&commitment).map_err(|e| RestError::BadRequest(format!("invalid commitment: {}", e)))?;
+            provider.state_path(&commitment)
+        })
+    });
+    let program = warp::path!("program" / String).and(with_provider).and_then(|program_id: String, provider: QueryProvider<N>| {
+        relay(move || {
+            let program_id = ProgramID::<N>::from_str(&program_id).map_err(|e| RestError::BadRequest(format!("invalid program ID: {}", e)))?;
+            provider.program(&program_id)
+        })
+    });
+
+    warp::get().and(warp::path(network)).and(latest_state_root.or(state_path).or(program))
+}
+
+/// Answers a relayed query with the JSON value, or the error and its status code.
+async fn relay<T: Serialize + Send + 'static>(fetch: impl FnOnce() -> Result<T, RestError> + Send + 'static) -> Result<warp::reply::Response, Infallible> {
+    let result = tokio::task::spawn_blocking(fetch)
+        .await
+        .unwrap_or_else(|e| Err(RestError::Query(format!("the query panicked: {}", e))));
+    Ok(match result {
+        Ok(value) => warp::reply::json(&value).into_response(),
+        Err(error) => warp::reply::with_status(warp::reply::json(&error.to_response()), error.status()).into_response(),
+    })
+}
+
+/// A backend serving a fixed chain from memory, so tests run offline.
+#[cfg(test)]
+pub struct MockBackend<N: Network> {
+    /// The global state root after each block, the last one is the latest.
+    pub state_roots: Vec<N::StateRoot>,
+    pub programs: Vec<Program<N>>,
+    /// The number of latest state roots served.
+    pub state_root_fetches: AtomicUsize,
+}
+
+#[cfg(test)]
+impl<N: Network> MockBackend<N> {
+    pub fn new(state_roots: Vec<N::StateRoot>, programs: Vec<Program<N>>) -> Self {
+        Self { state_roots, programs, state_root_fetches: AtomicUsize::new(0) }
+    }
+}
+
+#[cfg(test)]
+impl<N: Network> QueryBackend<N> for MockBackend<N> {
+    fn latest_state_root(&self) -> Result<N::StateRoot, RestError> {
+        self.state_root_fetches.fetch_add(1, Ordering::Relaxed);
+        self.state_roots.last().copied().ok_or_else(|| RestError::Query("the chain is empty".to_string()))
+    }
+
+    fn latest_height(&self) -> Result<u32, RestError> {
+        u32::try_from(self.state_roots.len()).ok().and_then(|len| len.checked_sub(1)).ok_or_else(|| RestError::Query("the chain is empty".to_string()))
+    }
+
+    fn previous_state_root(&self, height: u32) -> Result<N::StateRoot, RestError> {
+        let previous = (height as usize).checked_sub(1).and_then(|height| self.state_roots.get(height));
+        previous.copied().ok_or_else(|| RestError::Query(format!("block {} does not exist", height)))
+    }
+
+    fn state_path(&self, commitment: &Field<N>) -> Result<StatePath<N>, RestError> {
+        Err(RestError::Query(format!("no state path for '{}'", commitment)))
+    }
+
+    fn program(&self, program_id: &ProgramID<N>) -> Result<Program<N>, RestError> {
+        let program = self.programs.iter().find(|program| program.id() == program_id);
+        program.cloned().ok_or_else(|| RestError::UnknownProgram(format!("'{}' is not deployed", program_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console_network::prelude::Uniform;
+    use snarkvm_synthesizer::ConsensusMemory;
+    use crate::CurrentNetwork;
+
+    type StateRoot = <CurrentNetwork as Network>::StateRoot;
+    type Blocks = <ConsensusMemory<CurrentNetwork> as snarkvm_synthesizer::ConsensusStorage<CurrentNetwork>>::BlockStorage;
+
+    /// Serves the latest state root and `credits.aleo` like a node.
+    async fn serve_node(state_root: StateRoot) -> String {
+        let latest_root = warp::path!("testnet3" / "latest" / "stateRoot").map(move || warp::reply::json(&state_root));
+        let program = warp::path!("testnet3" / "program" / String).map(|id: String| match id.as_str() {
+            "credits.aleo" => warp::reply::with_status(warp::reply::json(&Program::<CurrentNetwork>::credits().unwrap()), warp::http::StatusCode::OK),
+            _ => warp::reply::with_status(warp::reply::json(&"not found"), warp::http::StatusCode::NOT_FOUND),
+        });
+        let (addr, server) = warp::serve(latest_root.or(program)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rest_backend_fails_over() {
+        let state_root = StateRoot::rand(&mut rand::thread_rng());
+        let endpoint = serve_node(state_root).await;
+
+        let results = tokio::task::spawn_blocking(move || {
+            let backend = RestBackend::<CurrentNetwork>::new(vec!["http://127.0.0.1:1".to_string(), endpoint]).unwrap();
+            let latest = backend.latest_state_root();
+            let preferred = backend.preferred.load(Ordering::Relaxed);
+            let credits = backend.program(&ProgramID::from_str("credits.aleo").unwrap());
+            let unknown = backend.program(&ProgramID::from_str("nope.aleo").unwrap());
+            let dead = RestBackend::<CurrentNetwork>::new(vec!["http://127.0.0.1:1".to_string()]).unwrap().latest_height();
+            (latest, preferred, credits, unknown, dead)
+        })
+        .await
+        .unwrap();
+
+        let (latest, preferred, credits, unknown, dead) = results;
+        assert_eq!(latest.unwrap(), state_root);
+        assert_eq!(preferred, 1);
+        assert_eq!(credits.unwrap().id().to_string(), "credits.aleo");
+        assert!(matches!(unknown, Err(RestError::UnknownProgram(_))));
+        assert!(matches!(dead, Err(RestError::Query(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_provider_caches_the_state_root() {
+        let state_root = StateRoot::rand(&mut rand::thread_rng());
+        let backend = Arc::new(MockBackend::<CurrentNetwork>::new(vec![state_root], vec![]));
+        let provider = QueryProvider::new(backend.clone(), Duration::from_secs(60)).unwrap();
+
+        let relayed = tokio::task::spawn_blocking(move || {
+            assert_eq!(provider.latest_state_root().unwrap(), state_root);
+            assert_eq!(provider.latest_state_root().unwrap(), state_root);
+            // snarkVM reads the cached state root through the relay.
+            provider.query::<Blocks>().current_state_root().unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(relayed, state_root);
+        assert_eq!(backend.state_root_fetches.load(Ordering::Relaxed), 1);
+    }
+}
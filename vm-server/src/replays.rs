@@ -0,0 +1,269 @@
+//! Replay protection: a signed request is proved once, and a record is spent by one request.
+//!
+//! Requests are keyed by their transition view key, which is unique to each signature. A request
+//! submitted again with the same fee returns the transaction proved for it, and a request spending
+//! a record another request spends within the window is refused. Fees proved on their own are
+//! claimed by their fee request, and keep their record spent for the window.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use snarkvm_console_network::Network;
+use snarkvm_console_program::{Field, InputID, Request};
+use snarkvm_synthesizer::Transaction;
+use crate::error::RestError;
+
+/// The requests being proved or proved within the window, shared by the routes. `T` is what is
+/// returned to repeated submissions.
+#[derive(Clone)]
+pub struct Replays<N: Network, T = Transaction<N>> {
+    inner: Arc<Mutex<Inner<N, T>>>,
+    window: Duration,
+}
+
+struct Inner<N: Network, T> {
+    requests: HashMap<Field<N>, Entry<N, T>>,
+    /// The serial numbers of the records spent, and the request spending them.
+    spent: HashMap<Field<N>, Field<N>>,
+}
+
+struct Entry<N: Network, T> {
+    /// The transition view key of the fee request, if the request was proved with a fee.
+    fee_tvk: Option<Field<N>>,
+    serial_numbers: Vec<Field<N>>,
+    /// When the request was proved, and its transaction unless the claim was kept without one.
+    proved: Option<(Option<T>, Instant)>,
+}
+
+/// The outcome of claiming a request.
+pub enum Claim<N: Network, T = Transaction<N>> {
+    /// The request is new, and is released unless its transaction is recorded.
+    New(ClaimGuard<N, T>),
+    /// The request was proved with the same fee within the window.
+    Proved(Box<T>),
+}
+
+/// A request being proved. Dropping it without a transaction releases its records.
+pub struct ClaimGuard<N: Network, T = Transaction<N>> {
+    replays: Replays<N, T>,
+    tvk: Field<N>,
+    finished: bool,
+}
+
+impl<N: Network, T: Clone> Replays<N, T> {
+    /// Initializes the replay protection, proved requests are remembered for the `window`.
+    pub fn new(window: Duration) -> Self {
+        Self { inner: Arc::new(Mutex::new(Inner { requests: HashMap::new(), spent: HashMap::new() })), window }
+    }
+
+    /// Claims the requests of an execution and the records they spend, with those of its fee. The
+    /// execution is keyed by its first request.
+    pub fn claim(&self, requests: &[Request<N>], fee_request: Option<&Request<N>>) -> Result<Claim<N, T>, RestError> {
+        let request = requests.first().ok_or_else(|| RestError::BadRequest("no request to execute".to_string()))?;
+        let mut inner = self.inner.lock();
+        inner.prune(self.window);
+
+        let tvk = *request.tvk();
+        let fee_tvk = fee_request.map(|fee_request| *fee_request.tvk());
+        if let Some(entry) = inner.requests.get(&tvk) {
+            return match &entry.proved {
+                _ if entry.fee_tvk != fee_tvk => Err(RestError::Conflict("the same request is proved with another fee".to_string())),
+                Some((Some(transaction), _)) => Ok(Claim::Proved(Box::new(transaction.clone()))),
+                Some((None, _)) => Err(RestError::Conflict("the same request was already proved".to_string())),
+                None => Err(RestError::Duplicate("the same request is already being proved".to_string())),
+            };
+        }
+        let serial_numbers = requests.iter().chain(fee_request).flat_map(serial_numbers).collect::<Vec<_>>();
+        self.insert(&mut inner, tvk, fee_tvk, serial_numbers).map(Claim::New)
+    }
+
+    /// Claims a fee request proved on its own and the record it spends, the fee is keyed by its
+    /// request.
+    pub fn claim_fee(&self, fee_request: &Request<N>) -> Result<ClaimGuard<N, T>, RestError> {
+        let mut inner = self.inner.lock();
+        inner.prune(self.window);
+
+        let tvk = *fee_request.tvk();
+        if let Some(entry) = inner.requests.get(&tvk) {
+            return match entry.proved {
+                Some(_) => Err(RestError::Conflict("the same fee request was already proved".to_string())),
+                None => Err(RestError::Duplicate("the same fee request is already being proved".to_string())),
+            };
+        }
+        self.insert(&mut inner, tvk, None, serial_numbers(fee_request))
+    }
+
+    /// Records a new request spending the records with the serial numbers, unless another request spends one.
+    fn insert(&self, inner: &mut Inner<N, T>, tvk: Field<N>, fee_tvk: Option<Field<N>>, serial_numbers: Vec<Field<N>>) -> Result<ClaimGuard<N, T>, RestError> {
+        if let Some(serial_number) = serial_numbers.iter().find(|serial_number| inner.spent.contains_key(serial_number)) {
+            return Err(RestError::Conflict(format!("the record with serial number '{}' is spent by another request", serial_number)));
+        }
+
+        for serial_number in &serial_numbers {
+            inner.spent.insert(*serial_number, tvk);
+        }
+        inner.requests.insert(tvk, Entry { fee_tvk, serial_numbers, proved: None });
+        Ok(ClaimGuard { replays: self.clone(), tvk, finished: false })
+    }
+}
+
+impl<N: Network, T> Inner<N, T> {
+    /// Forgets the requests proved before the window.
+    fn prune(&mut self, window: Duration) {
+        let expired = self
+            .requests
+            .iter()
+            .filter(|(_, entry)| matches!(entry.proved, Some((_, proved_at)) if proved_at.elapsed() >= window))
+            .map(|(tvk, _)| *tvk)
+            .collect::<Vec<_>>();
+        for tvk in expired {
+            self.remove(&tvk);
+        }
+    }
+
+    fn remove(&mut self, tvk: &Field<N>) {
+        if let Some(entry) = self.requests.remove(tvk) {
+            for serial_number in entry.serial_numbers {
+                self.spent.remove(&serial_number);
+            }
+        }
+    }
+}
+
+impl<N: Network, T: Clone> ClaimGuard<N, T> {
+    /// Records the transaction proved for the request, returned to repeated submissions.
+    pub fn finish(self, transaction: &T) {
+        self.record(Some(transaction.clone()));
+    }
+
+    /// Marks the request proved without a transaction to return, its records stay spent for the window.
+    pub fn keep(self) {
+        self.record(None);
+    }
+
+    fn record(mut self, transaction: Option<T>) {
+        if let Some(entry) = self.replays.inner.lock().requests.get_mut(&self.tvk) {
+            entry.proved = Some((transaction, Instant::now()));
+        }
+        self.finished = true;
+    }
+}
+
+impl<N: Network, T> Drop for ClaimGuard<N, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.replays.inner.lock().remove(&self.tvk);
+        }
+    }
+}
+
+/// Returns the serial numbers of the records the request spends.
+fn serial_numbers<N: Network>(request: &Request<N>) -> Vec<Field<N>> {
+    request
+        .input_ids()
+        .iter()
+        .filter_map(|input_id| match input_id {
+            InputID::Record(_, _, serial_number, _) => Some(*serial_number),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console_account::{Address, PrivateKey};
+    use crate::harness::sign_credits;
+    use crate::CurrentNetwork;
+
+    /// Stands for the transaction proved for a request.
+    type Proved = Replays<CurrentNetwork, &'static str>;
+
+    fn claim(replays: &Proved, request: &Request<CurrentNetwork>, fee_request: Option<&Request<CurrentNetwork>>) -> ClaimGuard<CurrentNetwork, &'static str> {
+        match replays.claim(std::slice::from_ref(request), fee_request) {
+            Ok(Claim::New(claim)) => claim,
+            _ => panic!("the request is not new"),
+        }
+    }
+
+    #[test]
+    fn test_replays() {
+        let rng = &mut rand::thread_rng();
+        let private_key = PrivateKey::<CurrentNetwork>::new(rng).unwrap();
+        let owner = Address::try_from(&private_key).unwrap();
+        let record = |gates: u64| format!("{{ owner: {}.private, gates: {}u64.private, _nonce: 0group.public }}", owner, gates);
+        let mut sign = |function: &str, record: String, amount: &str| {
+            let inputs = match function {
+                "transfer" => vec![record, owner.to_string(), amount.to_string()],
+                _ => vec![record, amount.to_string()],
+            };
+            sign_credits(&private_key, function, &inputs, rng)
+        };
+        let (request, conflicting, other) = (sign("transfer", record(100), "1u64"), sign("transfer", record(100), "2u64"), sign("transfer", record(200), "1u64"));
+        let (fee, other_fee) = (sign("fee", record(300), "10u64"), sign("fee", record(400), "10u64"));
+
+        // The request is refused while it is proved, and so is another request spending its record.
+        let replays = Proved::new(Duration::from_secs(60));
+        let proving = claim(&replays, &request, Some(&fee));
+        assert!(matches!(replays.claim(std::slice::from_ref(&request), Some(&fee)), Err(RestError::Duplicate(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&conflicting), None), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&other), Some(&fee)), Err(RestError::Conflict(_))));
+
+        // Once proved, the transaction is returned to repeated submissions with the same fee only.
+        proving.finish(&"transfer");
+        assert!(matches!(replays.claim(std::slice::from_ref(&request), Some(&fee)), Ok(Claim::Proved(proved)) if *proved == "transfer"));
+        assert!(matches!(replays.claim(std::slice::from_ref(&request), Some(&other_fee)), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&request), None), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&conflicting), None), Err(RestError::Conflict(_))));
+
+        // A request whose proof fails is released, with its fee record.
+        drop(claim(&replays, &other, Some(&other_fee)));
+        drop(claim(&replays, &other, Some(&other_fee)));
+        assert_eq!(replays.inner.lock().requests.len(), 1);
+        assert_eq!(replays.inner.lock().spent.len(), 2);
+
+        // Proved requests are forgotten after the window.
+        let replays = Proved::new(Duration::ZERO);
+        claim(&replays, &request, None).finish(&"transfer");
+        claim(&replays, &conflicting, None);
+    }
+
+    #[test]
+    fn test_fee_claims() {
+        let rng = &mut rand::thread_rng();
+        let private_key = PrivateKey::<CurrentNetwork>::new(rng).unwrap();
+        let owner = Address::try_from(&private_key).unwrap();
+        let record = format!("{{ owner: {}.private, gates: 300u64.private, _nonce: 0group.public }}", owner);
+        let fee = sign_credits(&private_key, "fee", &[record.clone(), "10u64".to_string()], rng);
+        let other_fee = sign_credits(&private_key, "fee", &[record.clone(), "20u64".to_string()], rng);
+        let transfer = sign_credits(&private_key, "transfer", &[record, owner.to_string(), "1u64".to_string()], rng);
+
+        // A fee is refused while it is proved, and its record cannot pay another fee or execution.
+        let replays = Proved::new(Duration::from_secs(60));
+        let proving = replays.claim_fee(&fee).unwrap();
+        assert!(matches!(replays.claim_fee(&fee), Err(RestError::Duplicate(_))));
+        assert!(matches!(replays.claim_fee(&other_fee), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&transfer), None), Err(RestError::Conflict(_))));
+
+        // A fee whose proof fails is released.
+        drop(proving);
+        let proving = replays.claim_fee(&fee).unwrap();
+
+        // Once proved, the record stays spent for the window.
+        proving.keep();
+        assert!(matches!(replays.claim_fee(&fee), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim_fee(&other_fee), Err(RestError::Conflict(_))));
+        assert!(matches!(replays.claim(std::slice::from_ref(&fee), None), Err(RestError::Conflict(_))));
+
+        // A record spent by an execution cannot pay a fee on its own.
+        let replays = Proved::new(Duration::from_secs(60));
+        let _proving = claim(&replays, &transfer, None);
+        assert!(matches!(replays.claim_fee(&fee), Err(RestError::Conflict(_))));
+
+        // Kept fees are forgotten after the window.
+        let replays = Proved::new(Duration::ZERO);
+        replays.claim_fee(&fee).unwrap().keep();
+        replays.claim_fee(&other_fee).unwrap();
+    }
+}
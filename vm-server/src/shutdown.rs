@@ -0,0 +1,46 @@
+use std::time::Duration;
+use snarkvm_console_network::Network;
+use tracing::{info, warn};
+use crate::jobs::Jobs;
+
+/// Resolves once SIGTERM or SIGINT is received and the proving jobs are drained.
+///
+/// New jobs are refused from the signal on, while the queued and proving ones finish. Jobs still
+/// unfinished after `timeout` are failed, so waiting clients get an answer before the server stops.
+pub async fn drain_on_signal<N: Network>(jobs: Jobs<N>, timeout: Duration) {
+    wait_for_signal().await;
+    info!("Shutting down, draining {} jobs for up to {}s", jobs.depth(), timeout.as_secs());
+    jobs.drain();
+    if !jobs.wait_idle(timeout).await {
+        warn!("Aborting {} jobs still running after {}s", jobs.depth(), timeout.as_secs());
+        jobs.abort_unfinished();
+    }
+}
+
+async fn wait_for_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
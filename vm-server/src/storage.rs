@@ -0,0 +1,255 @@
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use snarkvm_console_network::Network;
+use snarkvm_console_program::ProgramID;
+use snarkvm_synthesizer::{Process, Program, Stack};
+
+/// The storage backends for the programs and circuit keys loaded by the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// Nothing survives a restart.
+    #[default]
+    Memory,
+    /// Programs and keys are kept in a directory, requires the `disk` feature.
+    Disk,
+}
+
+/// Keeps the loaded programs and their synthesized keys across restarts.
+///
+/// Ledger state is always read from the query endpoint, so this only holds what the
+/// process would otherwise have to fetch or synthesize again.
+#[derive(Clone, Debug)]
+pub struct Storage<N: Network> {
+    /// The storage directory, `None` for the memory backend.
+    #[cfg_attr(not(feature = "disk"), allow(dead_code))]
+    path: Option<PathBuf>,
+    _phantom: PhantomData<N>,
+}
+
+impl<N: Network> Storage<N> {
+    /// Returns the memory backend.
+    pub fn memory() -> Self {
+        Self { path: None, _phantom: PhantomData }
+    }
+
+    /// Opens the given backend, `path` defaults to `~/.aleo/vm-server/{network}` for the disk backend.
+    pub fn open(kind: StorageKind, path: Option<PathBuf>) -> anyhow::Result<Self> {
+        match kind {
+            StorageKind::Memory => Ok(Self::memory()),
+            #[cfg(feature = "disk")]
+            StorageKind::Disk => {
+                let path = path.unwrap_or_else(|| aleo_std::aleo_dir().join("vm-server").join(N::ID.to_string()));
+                disk::create_dirs(&path)?;
+                Ok(Self { path: Some(path), _phantom: PhantomData })
+            }
+            #[cfg(not(feature = "disk"))]
+            StorageKind::Disk => {
+                let _ = path;
+                anyhow::bail!("the disk storage backend requires building vm-server with the `disk` feature")
+            }
+        }
+    }
+
+    /// Adds the stored programs and their keys to the process, returns the IDs of the programs.
+    pub fn restore(&self, process: &mut Process<N>) -> anyhow::Result<Vec<ProgramID<N>>> {
+        #[cfg(feature = "disk")]
+        if let Some(path) = &self.path {
+            return disk::restore(path, process);
+        }
+        let _ = process;
+        Ok(Vec::new())
+    }
+
+    /// Stores the program.
+    pub fn save_program(&self, program: &Program<N>) -> anyhow::Result<()> {
+        #[cfg(feature = "disk")]
+        if let Some(path) = &self.path {
+            return disk::save_program(path, program);
+        }
+        let _ = program;
+        Ok(())
+    }
+
+    /// Stores the keys of the stack and its imports that are not stored yet.
+    pub fn save_keys(&self, stack: &Stack<N>) -> anyhow::Result<()> {
+        #[cfg(feature = "disk")]
+        if let Some(path) = &self.path {
+            return disk::save_keys(path, stack);
+        }
+        let _ = stack;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "disk")]
+mod disk {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+    use anyhow::{bail, Context};
+    use snarkvm_console_network::prelude::{FromBytes, ToBytes};
+    use snarkvm_console_network::Network;
+    use snarkvm_console_program::{Identifier, ProgramID};
+    use snarkvm_synthesizer::{Process, Program, ProvingKey, Stack, VerifyingKey};
+
+    pub(super) fn create_dirs(path: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(path.join("programs"))
+            .and_then(|_| fs::create_dir_all(path.join("keys")))
+            .with_context(|| format!("failed to create the storage directory '{}'", path.display()))
+    }
+
+    pub(super) fn restore<N: Network>(path: &Path, process: &mut Process<N>) -> anyhow::Result<Vec<ProgramID<N>>> {
+        let mut pending = Vec::new();
+        for entry in fs::read_dir(path.join("programs"))? {
+            let file = entry?.path();
+            if file.extension().and_then(|extension| extension.to_str()) == Some("aleo") {
+                let source = fs::read_to_string(&file)?;
+                pending.push(Program::<N>::from_str(&source).with_context(|| format!("invalid program '{}'", file.display()))?);
+            }
+        }
+
+        // Add the programs once their imports are loaded.
+        let mut restored = Vec::new();
+        while !pending.is_empty() {
+            let before = pending.len();
+            let mut index = 0;
+            while index < pending.len() {
+                if pending[index].imports().keys().all(|import| process.contains_program(import)) {
+                    let program = pending.remove(index);
+                    if !process.contains_program(program.id()) {
+                        process.add_program(&program)?;
+                        restore_keys(path, process.get_stack(program.id())?)?;
+                        restored.push(*program.id());
+                    }
+                } else {
+                    index += 1;
+                }
+            }
+            if pending.len() == before {
+                bail!("stored program '{}' has missing imports", pending[0].id());
+            }
+        }
+        Ok(restored)
+    }
+
+    pub(super) fn save_program<N: Network>(path: &Path, program: &Program<N>) -> anyhow::Result<()> {
+        write(&path.join("programs").join(program.id().to_string()), program.to_string().as_bytes())
+    }
+
+    pub(super) fn save_keys<N: Network>(path: &Path, stack: &Stack<N>) -> anyhow::Result<()> {
+        let program_id = stack.program_id();
+        // The 'credits.aleo' keys ship with the parameters.
+        if program_id.to_string() != "credits.aleo" {
+            for function_name in stack.program().functions().keys() {
+                let (prover, verifier) = key_paths(path, program_id, function_name);
+                if !prover.exists() && stack.contains_proving_key(function_name) {
+                    write(&prover, &stack.get_proving_key(function_name)?.to_bytes_le()?)?;
+                }
+                if !verifier.exists() && stack.contains_verifying_key(function_name) {
+                    write(&verifier, &stack.get_verifying_key(function_name)?.to_bytes_le()?)?;
+                }
+            }
+        }
+        for import in stack.program().imports().keys() {
+            save_keys(path, stack.get_external_stack(import)?)?;
+        }
+        Ok(())
+    }
+
+    fn restore_keys<N: Network>(path: &Path, stack: &Stack<N>) -> anyhow::Result<()> {
+        for function_name in stack.program().functions().keys() {
+            let (prover, verifier) = key_paths(path, stack.program_id(), function_name);
+            if prover.exists() {
+                stack.insert_proving_key(function_name, ProvingKey::from_bytes_le(&fs::read(&prover)?)?)?;
+            }
+            if verifier.exists() {
+                stack.insert_verifying_key(function_name, VerifyingKey::from_bytes_le(&fs::read(&verifier)?)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn key_paths<N: Network>(path: &Path, program_id: &ProgramID<N>, function_name: &Identifier<N>) -> (PathBuf, PathBuf) {
+        let directory = path.join("keys").join(program_id.to_string());
+        (directory.join(format!("{}.prover", function_name)), directory.join(format!("{}.verifier", function_name)))
+    }
+
+    /// Writes the file through a temporary file, so an interrupted write never leaves a partial key.
+    fn write(file: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = file.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, file).with_context(|| format!("failed to write '{}'", file.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CurrentNetwork;
+
+    #[cfg(not(feature = "disk"))]
+    #[test]
+    fn test_disk_requires_feature() {
+        assert!(Storage::<CurrentNetwork>::open(StorageKind::Disk, None).is_err());
+    }
+
+    #[cfg(feature = "disk")]
+    #[test]
+    fn test_disk_saves_programs() {
+        use std::str::FromStr;
+
+        let path = std::env::temp_dir().join(format!("vm-server-storage-{}", std::process::id()));
+        let storage = Storage::<CurrentNetwork>::open(StorageKind::Disk, Some(path.clone())).unwrap();
+        let program = Program::<CurrentNetwork>::from_str("program token.aleo;\n\nfunction mint:\n    input r0 as u64.private;\n    output r0 as u64.private;\n").unwrap();
+        storage.save_program(&program).unwrap();
+
+        let stored = std::fs::read_to_string(path.join("programs").join("token.aleo")).unwrap();
+        assert_eq!(Program::<CurrentNetwork>::from_str(&stored).unwrap(), program);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[cfg(feature = "disk")]
+    #[test]
+    #[ignore = "downloads the testnet3 proving parameters"]
+    fn test_disk_restores_programs_and_keys() {
+        use std::str::FromStr;
+        use circuit::AleoV0;
+        use snarkvm_console_network::prelude::ToBytes;
+        use snarkvm_console_program::Identifier;
+
+        let path = std::env::temp_dir().join(format!("vm-server-restore-{}", std::process::id()));
+        let token = Program::<CurrentNetwork>::from_str("program token.aleo;\n\nfunction mint:\n    input r0 as u64.private;\n    output r0 as u64.private;\n").unwrap();
+        let wallet = Program::<CurrentNetwork>::from_str("import token.aleo;\n\nprogram wallet.aleo;\n\nfunction hold:\n    input r0 as u64.private;\n    output r0 as u64.private;\n").unwrap();
+        let mint = Identifier::from_str("mint").unwrap();
+
+        // Load the programs and synthesize the keys of a function, as a first run would.
+        let storage = Storage::<CurrentNetwork>::open(StorageKind::Disk, Some(path.clone())).unwrap();
+        let mut process = Process::<CurrentNetwork>::load().unwrap();
+        for program in [&token, &wallet] {
+            process.add_program(program).unwrap();
+            storage.save_program(program).unwrap();
+        }
+        crate::keys::synthesize_keys::<CurrentNetwork, AleoV0>(process.get_stack(token.id()).unwrap(), &[mint]).unwrap();
+        // The keys of the imports are stored with those of the program.
+        storage.save_keys(process.get_stack(wallet.id()).unwrap()).unwrap();
+        let stack = process.get_stack(token.id()).unwrap();
+        let keys = (stack.get_proving_key(&mint).unwrap().to_bytes_le().unwrap(), stack.get_verifying_key(&mint).unwrap().to_bytes_le().unwrap());
+
+        // A restart restores the programs, imports first, with their keys.
+        let mut restarted = Process::<CurrentNetwork>::load().unwrap();
+        let restored = Storage::<CurrentNetwork>::open(StorageKind::Disk, Some(path.clone())).unwrap().restore(&mut restarted).unwrap();
+        assert_eq!(restored, vec![*token.id(), *wallet.id()]);
+        assert_eq!(restarted.get_program(wallet.id()).unwrap(), &wallet);
+        let stack = restarted.get_stack(token.id()).unwrap();
+        assert_eq!(stack.program(), &token);
+        assert_eq!((stack.get_proving_key(&mint).unwrap().to_bytes_le().unwrap(), stack.get_verifying_key(&mint).unwrap().to_bytes_le().unwrap()), keys);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
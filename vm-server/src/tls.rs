@@ -0,0 +1,213 @@
+//! TLS termination, serving the routes over HTTPS with a certificate reloaded when its files change.
+
+use std::convert::Infallible;
+use std::fs::File;
+use std::future::Future;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, ensure, Context};
+use parking_lot::{Mutex, RwLock};
+use rustls_pemfile::Item;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+use warp::hyper::server::accept;
+use warp::hyper::service::{make_service_fn, Service};
+use warp::hyper::{Body, Request, Response, Server};
+
+/// How long a client may take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The certificate served to clients, read from PEM files.
+pub struct Certificates {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    /// The last modification time of the files, when they were last read.
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl Certificates {
+    /// Reads the certificate chain and its private key, in PKCS#8, PKCS#1 or SEC1 form.
+    pub fn load(cert_path: &Path, key_path: &Path) -> anyhow::Result<Arc<Self>> {
+        let modified = modified(cert_path, key_path);
+        let current = read_certified_key(cert_path, key_path)?;
+        Ok(Arc::new(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Arc::new(current)),
+            modified: Mutex::new(modified),
+        }))
+    }
+
+    /// Returns the certificate currently served.
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().clone()
+    }
+
+    /// Reads the files again if they changed since they were last read, and returns `true` if the
+    /// certificate was replaced. A certificate that fails to load is logged, and the previous one kept.
+    pub fn reload_if_changed(&self) -> bool {
+        let modified = modified(&self.cert_path, &self.key_path);
+        let mut last = self.modified.lock();
+        if modified.is_none() || modified == *last {
+            return false;
+        }
+        *last = modified;
+        match read_certified_key(&self.cert_path, &self.key_path) {
+            Ok(certified_key) => {
+                *self.current.write() = Arc::new(certified_key);
+                info!("Reloaded the TLS certificate from '{}'", self.cert_path.display());
+                true
+            }
+            Err(e) => {
+                warn!("Failed to reload the TLS certificate, keeping the previous one: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Checks the files for changes every `interval`, in the background.
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let certificates = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                certificates.reload_if_changed();
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+/// Serves the service over TLS on `bind` until `shutdown` resolves, then waits for the open
+/// connections to close.
+pub async fn serve<S>(service: S, bind: SocketAddr, certificates: Arc<Certificates>, shutdown: impl Future<Output = ()>) -> anyhow::Result<()>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let listener = TcpListener::bind(bind).await.with_context(|| format!("failed to listen on '{}'", bind))?;
+    let mut config = ServerConfig::builder().with_safe_defaults().with_no_client_auth().with_cert_resolver(certificates);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    info!("Listening on https://{}", bind);
+
+    // Handshakes run in their own tasks, so a slow client does not hold up the others.
+    let (sender, receiver) = mpsc::channel(64);
+    let accepting = tokio::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let (acceptor, sender) = (acceptor.clone(), sender.clone());
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = sender.send(stream).await;
+                    }
+                    Ok(Err(e)) => debug!("TLS handshake with '{}' failed: {}", addr, e),
+                    Err(_) => debug!("TLS handshake with '{}' timed out", addr),
+                }
+            });
+        }
+    });
+    let connections = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|stream| (Ok::<_, std::io::Error>(stream), receiver))
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        async move { Ok::<_, Infallible>(service) }
+    });
+    let result = Server::builder(accept::from_stream(connections)).serve(make_service).with_graceful_shutdown(shutdown).await;
+    accepting.abort();
+    result.context("the TLS server failed")
+}
+
+/// Returns the latest modification time of the files, or `None` if one cannot be read.
+fn modified(cert_path: &Path, key_path: &Path) -> Option<SystemTime> {
+    let cert = std::fs::metadata(cert_path).and_then(|metadata| metadata.modified()).ok()?;
+    let key = std::fs::metadata(key_path).and_then(|metadata| metadata.modified()).ok()?;
+    Some(cert.max(key))
+}
+
+fn read_certified_key(cert_path: &Path, key_path: &Path) -> anyhow::Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?).with_context(|| format!("failed to read '{}'", cert_path.display()))?;
+    ensure!(!certs.is_empty(), "'{}' contains no certificate", cert_path.display());
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .with_context(|| format!("failed to read '{}'", key_path.display()))?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("'{}' contains no private key", key_path.display()))?;
+    let key = sign::any_supported_type(&PrivateKey(key)).map_err(|_| anyhow!("the private key in '{}' is not supported", key_path.display()))?;
+    Ok(CertifiedKey::new(certs.into_iter().map(Certificate).collect(), key))
+}
+
+fn open(path: &Path) -> anyhow::Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("failed to open '{}'", path.display()))?;
+    Ok(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a new self-signed certificate and its key, and returns the certificate.
+    fn write_certificate(cert_path: &Path, key_path: &Path, modified: SystemTime) -> Vec<u8> {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(cert_path, certificate.serialize_pem().unwrap()).unwrap();
+        std::fs::write(key_path, certificate.serialize_private_key_pem()).unwrap();
+        for path in [cert_path, key_path] {
+            File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        }
+        certificate.serialize_der().unwrap()
+    }
+
+    #[test]
+    fn test_certificate_reload() {
+        let dir = std::env::temp_dir().join(format!("vm-server-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let epoch = SystemTime::UNIX_EPOCH;
+
+        let first = write_certificate(&cert_path, &key_path, epoch + Duration::from_secs(1));
+        let certificates = Certificates::load(&cert_path, &key_path).unwrap();
+        assert_eq!(certificates.current().cert[0].0, first);
+        assert!(!certificates.reload_if_changed());
+
+        let second = write_certificate(&cert_path, &key_path, epoch + Duration::from_secs(2));
+        assert!(certificates.reload_if_changed());
+        assert_eq!(certificates.current().cert[0].0, second);
+
+        // A broken key is not served, the previous certificate is kept.
+        std::fs::write(&key_path, "not a key").unwrap();
+        assert!(!certificates.reload_if_changed());
+        assert_eq!(certificates.current().cert[0].0, second);
+        assert!(Certificates::load(&cert_path, &key_path).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
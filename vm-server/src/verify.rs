@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use circuit::Aleo;
+use snarkvm_console_network::Network;
+use snarkvm_synthesizer::{ConsensusStorage, Fee, Stack, Transaction, VM};
+use crate::error::RestError;
+use crate::query::QueryProvider;
+
+/// Verifies the transaction like a node does before accepting it.
+///
+/// The server keeps no ledger, so the global state roots of the transaction are checked against
+/// the queried nodes instead: each must be the latest state root or one of the `window` before it.
+pub fn verify_transaction<N: Network, A: Aleo<Network = N>, C: ConsensusStorage<N>>(
+    vm: &VM<N, C>,
+    transaction: &Transaction<N>,
+    query: &QueryProvider<N>,
+    window: u32,
+) -> Result<(), RestError> {
+    let invalid = |e: anyhow::Error| RestError::Verification(format!("{:#}", e));
+
+    // Ensure the transaction ID is correct.
+    if *transaction.id() != transaction.to_root().map_err(invalid)? {
+        return Err(RestError::Verification(format!("incorrect transaction ID ({})", transaction.id())));
+    }
+    // Ensure no element is spent or created twice.
+    let duplicates = [
+        ("transition IDs", has_duplicates(transaction.transition_ids())),
+        ("transition public keys", has_duplicates(transaction.transition_public_keys())),
+        ("serial numbers", has_duplicates(transaction.serial_numbers())),
+        ("commitments", has_duplicates(transaction.commitments())),
+        ("nonces", has_duplicates(transaction.nonces())),
+    ];
+    if let Some((name, _)) = duplicates.iter().find(|(_, duplicate)| *duplicate) {
+        return Err(RestError::Verification(format!("the transaction contains duplicate {}", name)));
+    }
+
+    // Verify the proofs, then the state roots they were proved against.
+    let process = vm.process();
+    let process = process.read();
+    let mut state_roots = Vec::new();
+    match transaction {
+        Transaction::Deploy(_, deployment, fee) => {
+            Transaction::check_deployment_size(deployment).map_err(invalid)?;
+            // The program may already be loaded, so the deployment is checked on a fresh stack.
+            let stack = Stack::new(&process, deployment.program()).map_err(invalid)?;
+            stack.verify_deployment::<A, _>(deployment, &mut rand::thread_rng()).map_err(invalid)?;
+            process.verify_fee(fee).map_err(invalid)?;
+            state_roots.push(fee.global_state_root());
+        }
+        Transaction::Execute(_, execution, fee) => {
+            Transaction::check_execution_size(execution).map_err(invalid)?;
+            process.verify_execution::<true>(execution).map_err(invalid)?;
+            state_roots.push(execution.global_state_root());
+            if let Some(fee) = fee {
+                process.verify_fee(fee).map_err(invalid)?;
+                state_roots.push(fee.global_state_root());
+            }
+        }
+    }
+    drop(process);
+
+    state_roots.dedup();
+    for state_root in state_roots {
+        check_state_root(query, &state_root, window)?;
+    }
+    Ok(())
+}
+
+/// Verifies a fee proved on its own, like the fee of a transaction.
+pub fn verify_fee<N: Network, C: ConsensusStorage<N>>(vm: &VM<N, C>, fee: &Fee<N>, query: &QueryProvider<N>, window: u32) -> Result<(), RestError> {
+    vm.process().read().verify_fee(fee).map_err(|e| RestError::Verification(format!("{:#}", e)))?;
+    check_state_root(query, &fee.global_state_root(), window)
+}
+
+/// Ensures the global state root is the latest one, or one of the `window` before it.
+pub fn check_state_root<N: Network>(query: &QueryProvider<N>, state_root: &N::StateRoot, window: u32) -> Result<(), RestError> {
+    // The cached state root may be older than the one the transaction was proved against.
+    if query.latest_state_root()? == *state_root || query.refresh_state_root()? == *state_root {
+        return Ok(());
+    }
+    let height = query.latest_height()?;
+    for height in (height.saturating_sub(window) + 1..=height).rev() {
+        if query.previous_state_root(height)? == *state_root {
+            return Ok(());
+        }
+    }
+    Err(RestError::Verification(format!("global state root '{}' is not among the latest {} state roots", state_root, window + 1)))
+}
+
+fn has_duplicates<T: Eq + Hash>(items: impl Iterator<Item = T>) -> bool {
+    let mut seen = HashSet::new();
+    !items.into_iter().all(|item| seen.insert(item))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use snarkvm_console_network::prelude::Uniform;
+    use crate::query::MockBackend;
+    use crate::CurrentNetwork;
+
+    type StateRoot = <CurrentNetwork as Network>::StateRoot;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_check_state_root() {
+        let rng = &mut rand::thread_rng();
+        let roots = (0..6).map(|_| StateRoot::rand(rng)).collect::<Vec<_>>();
+        // A chain of height 5, whose state root after block `h` is `roots[h]`.
+        let query = QueryProvider::new(Arc::new(MockBackend::new(roots.clone(), vec![])), Duration::from_secs(60)).unwrap();
+        let unknown = StateRoot::rand(rng);
+
+        let results = tokio::task::spawn_blocking(move || {
+            [
+                check_state_root(&query, &roots[5], 2),
+                check_state_root(&query, &roots[3], 2),
+                check_state_root(&query, &roots[2], 2),
+                check_state_root(&query, &unknown, 5),
+            ]
+        })
+        .await
+        .unwrap();
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(matches!(results[2], Err(RestError::Verification(_))));
+        assert!(matches!(results[3], Err(RestError::Verification(_))));
+    }
+
+    #[test]
+    fn test_has_duplicates() {
+        assert!(!has_duplicates([1, 2, 3].iter()));
+        assert!(has_duplicates([1, 2, 1].iter()));
+    }
+}
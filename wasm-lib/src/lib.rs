@@ -3,7 +3,7 @@ mod transfer;
 mod utils;
 
 use crate::records::{request_records_internal, RecordScanner};
-use crate::transfer::transfer_internal;
+use crate::transfer::{estimate_fee_internal, transfer_internal};
 use snarkvm_console_network::Testnet3;
 use wasm_bindgen::prelude::*;
 
@@ -76,3 +76,17 @@ pub async fn transfer(
         Err(e) => format!("error: {}", e),
     }
 }
+
+/// Returns the fee estimate of the transfer as JSON, `recommended_fee` prefills the fee.
+#[wasm_bindgen]
+pub async fn estimate_fee(
+    private_key: String,
+    record: String,
+    amount: u64,
+    recipient: String,
+) -> String {
+    match estimate_fee_internal::<CurrentNetwork>(private_key, record, amount, recipient).await {
+        Ok(estimate) => serde_json::to_string(&estimate).unwrap_or_else(|e| format!("error: {}", e)),
+        Err(e) => format!("error: {}", e),
+    }
+}
@@ -12,15 +12,69 @@ use snarkvm_console_network::Network;
 use snarkvm_console_program::{Identifier, Plaintext, Record, Request, U64, Value};
 use snarkvm_synthesizer::{Program, Transaction};
 use std::str::FromStr;
-use serde::{Serialize, Deserialize};
+use vm_protocol::{ErrorResponse, EstimateFeeRequest, ExecuteRequest, FeeEstimate};
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(bound = "")]
-pub struct TransferRequest<N: Network> {
-    request: Request<N>,
-    fee_request: Option<Request<N>>,
-    fee_record: Option<String>,
-    fee: Option<u64>,
+/// The base URL of the vm server.
+const VM_SERVER_URL: &str = "http://127.0.0.1:17777";
+
+/// Decodes a failed vm server response into an error carrying its real cause.
+pub(crate) fn decode_error(status: u16, body: &str) -> anyhow::Error {
+    match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(error) => anyhow::Error::msg(format!("vm server error: {}", error)),
+        Err(_) => anyhow::Error::msg(format!("vm server error: status {}: {}", status, body)),
+    }
+}
+
+/// Signs the `credits.aleo/transfer` request of `amount` gates from the record to the recipient.
+fn sign_transfer<N: Network, R: rand::Rng + rand::CryptoRng>(
+    program: &Program<N>,
+    private_key: &PrivateKey<N>,
+    record: &str,
+    amount: u64,
+    recipient: &str,
+    rng: &mut R,
+) -> anyhow::Result<Request<N>> {
+    let record = Record::<N, Plaintext<N>>::from_str(record)?;
+    let recipient = Address::<N>::from_str(recipient)?;
+
+    let inputs = vec![
+        Value::Record(record),
+        Value::from_str(&format!("{}", recipient))?,
+        Value::from_str(&format!("{}u64", amount))?,
+    ];
+
+    // Initialize the 'credits.aleo' program.
+    let function_name = Identifier::<N>::from_str("transfer")?;
+    let input_types = program.get_function(&function_name)?.input_types();
+
+    Request::<N>::sign(private_key, *program.id(), function_name, inputs.into_iter(), &input_types, rng)
+}
+
+/// Asks the vm server for the fee of the transfer, without proving it.
+pub(crate) async fn estimate_fee_internal<N: Network>(
+    private_key: String,
+    record: String,
+    amount: u64,
+    recipient: String,
+) -> anyhow::Result<FeeEstimate> {
+    let rng = &mut rand::thread_rng();
+    let program = Program::<N>::credits()?;
+    let private_key = PrivateKey::<N>::from_str(&private_key)?;
+    let request = sign_transfer(&program, &private_key, &record, amount, &recipient, rng)?;
+    request_fee_estimate(&reqwest::Client::new(), &request).await
+}
+
+/// Asks the vm server for the fee of the signed request, without proving it.
+async fn request_fee_estimate<N: Network>(client: &reqwest::Client, request: &Request<N>) -> anyhow::Result<FeeEstimate> {
+    let url = format!("{}/estimate_fee", VM_SERVER_URL);
+    let body = serde_json::to_string(&EstimateFeeRequest::new(request.clone())).map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    let response = client.post(url).body(body).send().await.map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    let status = response.status();
+    let response_body = response.text().await.map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    if !status.is_success() {
+        return Err(decode_error(status.as_u16(), &response_body));
+    }
+    Ok(serde_json::from_str(&response_body)?)
 }
 
 pub(crate) async fn transfer_internal<N: Network>(
@@ -34,49 +88,37 @@ pub(crate) async fn transfer_internal<N: Network>(
 ) -> anyhow::Result<String> {
     // Initialize an RNG.
     let rng = &mut rand::thread_rng();
-    let record = Record::<N, Plaintext<N>>::from_str(&record)?;
-    let recipient = Address::<N>::from_str(&recipient)?;
-
-    let inputs = vec![
-        Value::Record(record.clone()),
-        Value::from_str(&format!("{}", recipient))?,
-        Value::from_str(&format!("{}u64", amount))?,
-    ];
-
     let program = Program::<N>::credits()?;
-
-    // Initialize the 'credits.aleo' program.
-    let function_name = Identifier::<N>::from_str("transfer")?;
     // Retrieve the private key.
     let private_key = PrivateKey::<N>::from_str(&private_key)?;
-    let input_types = program.get_function(&function_name)?.input_types();
-
-    let request = Request::<N>::sign(&private_key, *program.id(), function_name, &mut inputs.into_iter(), &input_types, rng)?;
+    let request = sign_transfer(&program, &private_key, &record, amount, &recipient, rng)?;
 
-    let mut transfer_request = TransferRequest {
-        request,
-        fee_request: None,
-        fee_record: None,
-        fee: None,
-    };
+    let client = reqwest::Client::new();
+    let mut transfer_request = ExecuteRequest::new(request.clone());
 
     if let Some(fee_record) = fee_record {
+        // Pay the recommended fee if none is set, the server refuses fees below its minimum.
+        let fee = match fee {
+            Some(fee) => fee,
+            None => request_fee_estimate(&client, &request).await?.recommended_fee,
+        };
         let fee_record_raw = Record::<N, Plaintext<N>>::from_str(&fee_record)?;
-        let fee_inputs = [Value::Record(fee_record_raw), Value::from_str(&format!("{}", U64::<N>::new(fee.unwrap_or_default())))?];
+        let fee_inputs = [Value::Record(fee_record_raw), Value::from_str(&format!("{}", U64::<N>::new(fee)))?];
         let fee_function_name = Identifier::<N>::from_str("fee")?;
         let fee_input_types = program.get_function(&fee_function_name)?.input_types();
         let fee_request = Request::<N>::sign(&private_key, *program.id(), fee_function_name, &mut fee_inputs.into_iter(), &fee_input_types, rng)?;
-        transfer_request.fee = Some(fee.unwrap_or_default());
-        transfer_request.fee_record = Some(fee_record);
-        transfer_request.fee_request = Some(fee_request);
+        transfer_request = transfer_request.with_fee(fee_request, fee_record, fee);
     }
 
     // send to vm server
-    let client = reqwest::Client::new();
-    let url = "http://127.0.0.1:17777/execute_function";
+    let url = format!("{}/execute_function", VM_SERVER_URL);
     let body = serde_json::to_string(&transfer_request).map_err(|e| anyhow::Error::msg(e.to_string()))?;
     let response = client.post(url).body(body).send().await.map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    let status = response.status();
     let response_body = response.text().await.map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    if !status.is_success() {
+        return Err(decode_error(status.as_u16(), &response_body));
+    }
 
     // broadcast
     let transaction = Transaction::<CurrentNetwork>::from_str(&response_body)?;
@@ -115,12 +157,13 @@ mod tests {
 
         console_log!("{}", conf[3].clone());
 
+        // The fee is the one the server recommends.
         let msg = transfer_internal::<CurrentNetwork>(
             conf[0].clone(),
             conf[3].clone(),
             Some(conf[6].clone()),
             u64::from_str(&conf[4]).unwrap(),
-            Some(u64::from_str(&conf[7]).unwrap()),
+            None,
             conf[5].clone(),
             conf[2].clone(),
         )
@@ -143,13 +186,6 @@ mod tests {
     #[cfg(not(target_arch = "wasm32"))]
     #[tokio::test]
     async fn test_transfer() {
-        #[derive(Clone, Debug, Serialize, Deserialize)]
-        pub struct MyRequest {
-            request: Request<CurrentNetwork>,
-            fee_request: Option<Request<CurrentNetwork>>,
-            fee_record: Option<String>,
-            fee: Option<u64>,
-        }
         let file_contents = std::str::from_utf8(TRANSFER_CONF_DATA).unwrap();
         let conf = file_contents
             .split('\n')
@@ -178,22 +214,21 @@ mod tests {
 
         let request = Request::<CurrentNetwork>::sign(&private_key, *program.id(), function_name, &mut inputs.into_iter(), &input_types, rng).unwrap();
 
+        // Pay the fee the server recommends for the transfer.
+        let client = reqwest::Client::new();
+        let fee = request_fee_estimate(&client, &request).await.unwrap().recommended_fee;
+        assert!(fee > 0);
+
         let fee_record = Record::<CurrentNetwork, Plaintext<CurrentNetwork>>::from_str(&conf[6].clone()).unwrap();
-        let fee_inputs = [Value::Record(fee_record.clone()), Value::from_str(&format!("{}", U64::<CurrentNetwork>::new(200))).unwrap()];
+        let fee_inputs = [Value::Record(fee_record.clone()), Value::from_str(&format!("{}", U64::<CurrentNetwork>::new(fee))).unwrap()];
         let fee_function_name = Identifier::<CurrentNetwork>::from_str("fee").unwrap();
         let fee_input_types = program.get_function(&fee_function_name).unwrap().input_types();
         let fee_request = Request::<CurrentNetwork>::sign(&private_key, *program.id(), fee_function_name, &mut fee_inputs.into_iter(), &fee_input_types, rng).unwrap();
 
 
-        let req = MyRequest {
-            request,
-            fee_request: Some(fee_request),
-            fee_record: Some(fee_record.to_string()),
-            fee: Some(200),
-        };
+        let req = ExecuteRequest::new(request).with_fee(fee_request, fee_record.to_string(), fee);
 
-        let client = reqwest::Client::new();
-        let url = "http://127.0.0.1:17777/execute_function";
+        let url = format!("{}/execute_function", VM_SERVER_URL);
         let body = serde_json::to_string(&req).unwrap();
         let response = client.post(url).body(body).send().await.unwrap();
         let response_body = response.text().await.unwrap();
@@ -254,14 +289,19 @@ mod tests {
         let fee_request = Request::<CurrentNetwork>::sign(&private_key, *program.id(), fee_function_name, &mut fee_inputs.into_iter(), &fee_input_types, rng).unwrap();
 
 
-        let req = TransferRequest {
-            request,
-            fee_request: Some(fee_request),
-            fee_record: Some(fee_record.to_string()),
-            fee: Some(200),
-        };
+        let req = ExecuteRequest::new(request).with_fee(fee_request, fee_record.to_string(), 200);
         let result = serde_json::to_string(&req).unwrap();
-        let result2 = serde_json::from_str::<TransferRequest::<CurrentNetwork>>(&result).unwrap();
+        let result2 = serde_json::from_str::<ExecuteRequest::<CurrentNetwork>>(&result).unwrap();
         assert_eq!(req, result2)
     }
+
+    #[test]
+    fn test_decode_error() {
+        let body = r#"{"code":"invalid_record","message":"a record in the request is invalid","details":"Invalid record"}"#;
+        let error = decode_error(422, body).to_string();
+        assert_eq!(error, "vm server error: a record in the request is invalid (invalid_record): Invalid record");
+
+        let error = decode_error(500, "Unhandled rejection").to_string();
+        assert_eq!(error, "vm server error: status 500: Unhandled rejection");
+    }
 }